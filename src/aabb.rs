@@ -0,0 +1,142 @@
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+
+// Axis-aligned bounding box, stored as its minimum and maximum corners
+#[derive(Debug, Clone, Copy)]
+pub struct Aabb {
+    pub minimum: Vec3,
+    pub maximum: Vec3,
+}
+
+impl Aabb {
+    pub fn new(minimum: Vec3, maximum: Vec3) -> Aabb {
+        Aabb { minimum, maximum }
+    }
+
+    // Box that contains nothing; surrounding it with any box returns that box
+    pub fn empty() -> Aabb {
+        Aabb {
+            minimum: Vec3 {
+                x: f64::INFINITY,
+                y: f64::INFINITY,
+                z: f64::INFINITY,
+            },
+            maximum: Vec3 {
+                x: f64::NEG_INFINITY,
+                y: f64::NEG_INFINITY,
+                z: f64::NEG_INFINITY,
+            },
+        }
+    }
+
+    // Box that contains everything, used for objects without finite bounds
+    pub fn infinite() -> Aabb {
+        Aabb {
+            minimum: Vec3 {
+                x: f64::NEG_INFINITY,
+                y: f64::NEG_INFINITY,
+                z: f64::NEG_INFINITY,
+            },
+            maximum: Vec3 {
+                x: f64::INFINITY,
+                y: f64::INFINITY,
+                z: f64::INFINITY,
+            },
+        }
+    }
+
+    pub fn surrounding(self, other: Aabb) -> Aabb {
+        Aabb {
+            minimum: Vec3 {
+                x: self.minimum.x.min(other.minimum.x),
+                y: self.minimum.y.min(other.minimum.y),
+                z: self.minimum.z.min(other.minimum.z),
+            },
+            maximum: Vec3 {
+                x: self.maximum.x.max(other.maximum.x),
+                y: self.maximum.y.max(other.maximum.y),
+                z: self.maximum.z.max(other.maximum.z),
+            },
+        }
+    }
+
+    pub fn including(self, p: Vec3) -> Aabb {
+        self.surrounding(Aabb::new(p, p))
+    }
+
+    pub fn centroid(&self) -> Vec3 {
+        0.5 * (self.minimum + self.maximum)
+    }
+
+    pub fn extent(&self) -> Vec3 {
+        self.maximum - self.minimum
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.minimum.x > self.maximum.x
+            || self.minimum.y > self.maximum.y
+            || self.minimum.z > self.maximum.z
+    }
+
+    pub fn is_finite(&self) -> bool {
+        [self.minimum, self.maximum]
+            .iter()
+            .all(|p| p.x.is_finite() && p.y.is_finite() && p.z.is_finite())
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let d = self.extent();
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    pub fn longest_axis(&self) -> usize {
+        let d = self.extent();
+        if d.x >= d.y && d.x >= d.z {
+            0
+        } else if d.y >= d.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    // Slab test, narrowing [t_min, t_max] one axis at a time
+    pub fn hit(&self, r: &Ray, mut t_min: f64, mut t_max: f64) -> bool {
+        for axis in 0..3 {
+            let inv_d = 1.0 / r.direction[axis];
+            let mut t0 = (self.minimum[axis] - r.origin[axis]) * inv_d;
+            let mut t1 = (self.maximum[axis] - r.origin[axis]) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            t_min = if t0 > t_min { t0 } else { t_min };
+            t_max = if t1 < t_max { t1 } else { t_max };
+            if t_max < t_min {
+                return false;
+            }
+        }
+        true
+    }
+
+    // Part of [t_min, t_max] inside the box, or None if the ray misses it
+    pub fn clip(&self, r: &Ray, mut t_min: f64, mut t_max: f64) -> Option<(f64, f64)> {
+        for axis in 0..3 {
+            let inv_d = 1.0 / r.direction[axis];
+            let mut t0 = (self.minimum[axis] - r.origin[axis]) * inv_d;
+            let mut t1 = (self.maximum[axis] - r.origin[axis]) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            // NaN from 0 * inf leaves the bound unchanged
+            t_min = if t0 > t_min { t0 } else { t_min };
+            t_max = if t1 < t_max { t1 } else { t_max };
+            if t_max <= t_min {
+                return None;
+            }
+        }
+        Some((t_min, t_max))
+    }
+}
@@ -0,0 +1,48 @@
+use std::sync::Arc;
+
+use crate::environment::EnvironmentMap;
+use crate::ray::Ray;
+use crate::vec3::*;
+
+// Radiance seen by rays that escape the scene
+#[derive(Clone, Default)]
+pub enum Background {
+    // White-to-blue gradient from the original tutorial
+    #[default]
+    Sky,
+    // Constant color; black for scenes lit only by emissive surfaces
+    Solid(Vec3),
+    // HDR image around the scene, also sampled as a light
+    Environment(Arc<EnvironmentMap>),
+}
+
+impl Background {
+    pub fn black() -> Background {
+        Background::Solid(Vec3::zeros())
+    }
+
+    pub fn color(&self, r: &Ray) -> Vec3 {
+        match self {
+            Background::Sky => {
+                let unit_direction = r.direction.unit_vec();
+                let t = 0.5 * (unit_direction.y + 1.0);
+                (1.0 - t) * Vec3::ones()
+                    + t * Vec3 {
+                        x: 0.5,
+                        y: 0.7,
+                        z: 1.0,
+                    }
+            }
+            Background::Solid(color) => *color,
+            Background::Environment(map) => map.radiance(r.direction),
+        }
+    }
+
+    // The environment map, if the background should be sampled as a light
+    pub fn environment(&self) -> Option<&EnvironmentMap> {
+        match self {
+            Background::Environment(map) => Some(map),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use crate::aabb::Aabb;
+use crate::hit::*;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+
+// Number of centroid bins evaluated per split when building with the SAH
+const SAH_BUCKETS: usize = 16;
+// Cost of visiting an interior node relative to intersecting one primitive
+const TRAVERSAL_COST: f64 = 0.125;
+const MAX_LEAF_SIZE: usize = 4;
+
+pub struct BvhNode {
+    bbox: Aabb,
+    contents: BvhContents,
+}
+
+enum BvhContents {
+    Leaf(HittableList),
+    Interior {
+        left: Box<BvhNode>,
+        right: Box<BvhNode>,
+    },
+}
+
+struct BuildItem {
+    object: Hittable,
+    bbox: Aabb,
+    centroid: Vec3,
+}
+
+impl BvhNode {
+    // Builds a hierarchy over `list` using surface-area-heuristic splits.
+    // Objects without a bounding box are kept in a leaf next to the tree
+    // and are tested for every ray, and the tree then has no bounding box
+    // itself.
+    pub fn new(list: HittableList) -> BvhNode {
+        let mut bounded = Vec::new();
+        let mut unbounded = HittableList::new();
+
+        for object in list {
+            match object.bounding_box() {
+                Some(bbox) => bounded.push(BuildItem {
+                    object,
+                    bbox,
+                    centroid: bbox.centroid(),
+                }),
+                None => unbounded.push(object),
+            }
+        }
+
+        let tree = BvhNode::build(bounded);
+        if unbounded.is_empty() {
+            tree
+        } else {
+            BvhNode {
+                bbox: Aabb::infinite(),
+                contents: BvhContents::Interior {
+                    left: Box::new(tree),
+                    right: Box::new(BvhNode {
+                        bbox: Aabb::infinite(),
+                        contents: BvhContents::Leaf(unbounded),
+                    }),
+                },
+            }
+        }
+    }
+
+    fn leaf(items: Vec<BuildItem>, bbox: Aabb) -> BvhNode {
+        BvhNode {
+            bbox,
+            contents: BvhContents::Leaf(items.into_iter().map(|item| item.object).collect()),
+        }
+    }
+
+    fn build(items: Vec<BuildItem>) -> BvhNode {
+        let bbox = items
+            .iter()
+            .fold(Aabb::empty(), |acc, item| acc.surrounding(item.bbox));
+
+        if items.len() <= 1 {
+            return BvhNode::leaf(items, bbox);
+        }
+
+        let centroid_bounds = items
+            .iter()
+            .fold(Aabb::empty(), |acc, item| acc.including(item.centroid));
+        let axis = centroid_bounds.longest_axis();
+        let axis_min = centroid_bounds.minimum[axis];
+        let axis_extent = centroid_bounds.extent()[axis];
+
+        // All centroids coincide, so no plane separates them
+        if axis_extent <= 0.0 {
+            if items.len() <= MAX_LEAF_SIZE {
+                return BvhNode::leaf(items, bbox);
+            }
+            let mut left = items;
+            let right = left.split_off(left.len() / 2);
+            return BvhNode::interior(left, right, bbox);
+        }
+
+        let bucket_of = |centroid: Vec3| -> usize {
+            let offset = (centroid[axis] - axis_min) / axis_extent;
+            ((offset * SAH_BUCKETS as f64) as usize).min(SAH_BUCKETS - 1)
+        };
+
+        let mut bucket_counts = [0usize; SAH_BUCKETS];
+        let mut bucket_bounds = [Aabb::empty(); SAH_BUCKETS];
+        for item in &items {
+            let b = bucket_of(item.centroid);
+            bucket_counts[b] += 1;
+            bucket_bounds[b] = bucket_bounds[b].surrounding(item.bbox);
+        }
+
+        // Sweep from the right so each split's right-hand side is known in O(1)
+        let mut right_area = [0.0; SAH_BUCKETS];
+        let mut right_count = [0usize; SAH_BUCKETS];
+        let mut acc_bounds = Aabb::empty();
+        let mut acc_count = 0;
+        for b in (1..SAH_BUCKETS).rev() {
+            acc_bounds = acc_bounds.surrounding(bucket_bounds[b]);
+            acc_count += bucket_counts[b];
+            right_area[b] = acc_bounds.surface_area();
+            right_count[b] = acc_count;
+        }
+
+        let parent_area = bbox.surface_area();
+        let mut best_cost = f64::INFINITY;
+        let mut best_split = 0;
+        let mut acc_bounds = Aabb::empty();
+        let mut acc_count = 0;
+        for b in 0..SAH_BUCKETS - 1 {
+            acc_bounds = acc_bounds.surrounding(bucket_bounds[b]);
+            acc_count += bucket_counts[b];
+            if acc_count == 0 || right_count[b + 1] == 0 {
+                continue;
+            }
+            let cost = TRAVERSAL_COST
+                + (acc_bounds.surface_area() * acc_count as f64
+                    + right_area[b + 1] * right_count[b + 1] as f64)
+                    / parent_area;
+            if cost < best_cost {
+                best_cost = cost;
+                best_split = b;
+            }
+        }
+
+        let leaf_cost = items.len() as f64;
+        if items.len() <= MAX_LEAF_SIZE && leaf_cost <= best_cost {
+            return BvhNode::leaf(items, bbox);
+        }
+
+        let (left, right): (Vec<BuildItem>, Vec<BuildItem>) = items
+            .into_iter()
+            .partition(|item| bucket_of(item.centroid) <= best_split);
+        BvhNode::interior(left, right, bbox)
+    }
+
+    // Visits every object stored in the hierarchy's leaves
+    pub fn for_each_object<'a>(&'a self, f: &mut dyn FnMut(&'a Hittable)) {
+        match &self.contents {
+            BvhContents::Leaf(list) => list.iter().for_each(f),
+            BvhContents::Interior { left, right } => {
+                left.for_each_object(f);
+                right.for_each_object(f);
+            }
+        }
+    }
+
+    fn interior(left: Vec<BuildItem>, right: Vec<BuildItem>, bbox: Aabb) -> BvhNode {
+        BvhNode {
+            bbox,
+            contents: BvhContents::Interior {
+                left: Box::new(BvhNode::build(left)),
+                right: Box::new(BvhNode::build(right)),
+            },
+        }
+    }
+}
+
+impl Hit for BvhNode {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        if !self.bbox.hit(r, t_min, t_max) {
+            return None;
+        }
+
+        match &self.contents {
+            BvhContents::Leaf(list) => list.hit(r, t_min, t_max),
+            BvhContents::Interior { left, right } => {
+                let left_hit = left.hit(r, t_min, t_max);
+                let closest_t_so_far = left_hit.as_ref().map_or(t_max, |rec| rec.t);
+                let right_hit = right.hit(r, t_min, closest_t_so_far);
+                right_hit.or(left_hit)
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // An infinite box would make a parent's surface area costs infinite
+        if self.bbox.is_empty() || !self.bbox.is_finite() {
+            None
+        } else {
+            Some(self.bbox)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::sphere::Sphere;
+
+    fn sphere(x: f64) -> Hittable {
+        Sphere {
+            center: Vec3 { x, y: 0.0, z: 0.0 },
+            radius: 0.5,
+            material: Lambertian {
+                albedo: Vec3::ones().into(),
+            }
+            .into(),
+        }
+        .into()
+    }
+
+    #[test]
+    fn trees_with_unbounded_objects_have_no_bounding_box() {
+        // An empty tree has no bounds, so it goes in the unbounded leaf
+        let unbounded: Hittable = BvhNode::new(Vec::new()).into();
+        assert!(unbounded.bounding_box().is_none());
+        let tree = BvhNode::new(vec![unbounded, sphere(0.0)]);
+        assert!(tree.bounding_box().is_none());
+
+        // Objects on both sides of the parent are still found
+        let parent = BvhNode::new(vec![tree.into(), sphere(2.0), sphere(4.0)]);
+        assert!(parent.bounding_box().is_none());
+        for x in [0.0, 2.0, 4.0] {
+            let r = Ray {
+                origin: Vec3 { x, y: 0.0, z: -5.0 },
+                direction: Vec3 { x: 0.0, y: 0.0, z: 1.0 },
+                time: 0.0,
+            };
+            let rec = parent.hit(&r, 0.001, f64::INFINITY).unwrap();
+            assert!((rec.t - 4.5).abs() < 1e-9);
+        }
+    }
+}
@@ -1,20 +1,55 @@
+use enum_dispatch::enum_dispatch;
+use serde::{Deserialize, Serialize};
+
 use crate::ray::Ray;
 use crate::vec3::{Vec3, VecLength, VecProducts};
-use crate::uniform_wrapper::*;
-use rand::{prelude::ThreadRng, distributions::Uniform};
+use crate::sampler::{Sampler, SamplerEnum};
+
+#[enum_dispatch(CameraEnum)]
+pub trait Camera {
+    // Ray through the point (s, t) of the image, from the bottom left corner
+    // at (0, 0) to the top right at (1, 1). None where the projection
+    // doesn't cover the image, which is left black.
+    fn get_ray(&self, s: f64, t: f64, sampler: &mut SamplerEnum) -> Option<Ray>;
+}
+
+#[enum_dispatch]
+pub enum CameraEnum {
+    PerspectiveCamera,
+    OrthographicCamera,
+    FisheyeCamera,
+    EquirectangularCamera,
+}
 
-pub struct Camera {
+// Right, up and backwards, for a camera at look_from facing look_at
+fn basis(look_from: Vec3, look_at: Vec3, v_up: Vec3) -> (Vec3, Vec3, Vec3) {
+    let w = (look_from - look_at).unit_vec();
+    let u = v_up.cross(w).unit_vec();
+    let v = w.cross(u);
+    (u, v, w)
+}
+
+// Each ray is sent at a uniformly random time while the shutter is open
+fn shutter_time(time0: f64, time1: f64, sampler: &mut SamplerEnum) -> f64 {
+    time0 + sampler.get_1d() * (time1 - time0)
+}
+
+// Thin lens; depth of field when the aperture is above 0
+pub struct PerspectiveCamera {
     origin: Vec3,
     lower_left_corner: Vec3,
     horizontal: Vec3,
     vertical: Vec3,
     u: Vec3,
     v: Vec3,
-    w: Vec3,
-    lens_radius: f64
+    lens_radius: f64,
+    // Shutter open and close times
+    time0: f64,
+    time1: f64
 }
 
-impl Camera {
+impl PerspectiveCamera {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         look_from: Vec3,
         look_at: Vec3,
@@ -23,15 +58,15 @@ impl Camera {
         aspect_ratio: f64,
         aperture: f64,
         focus_dist: f64,
-    ) -> Camera {
+        time0: f64,
+        time1: f64,
+    ) -> PerspectiveCamera {
         let theta = v_fov.to_radians();
         let h = (theta / 2.0).tan();
         let viewport_height = h * 2.0;
         let viewport_width: f64 = aspect_ratio * viewport_height;
 
-        let w = (look_from - look_at).unit_vec();
-        let u = v_up.cross(w).unit_vec();
-        let v = w.cross(u);
+        let (u, v, w) = basis(look_from, look_at, v_up);
 
         let origin = look_from;
 
@@ -41,25 +76,200 @@ impl Camera {
 
         let lens_radius = aperture / 2.0;
 
-        Camera {
+        PerspectiveCamera {
             origin,
             lower_left_corner,
             horizontal,
             vertical,
             u,
             v,
-            w,
-            lens_radius
+            lens_radius,
+            time0,
+            time1
         }
     }
+}
 
-    pub fn get_ray(&self, s: f64, t: f64, unigen: &mut UniGenNeg1_1) -> Ray {
-        let rd = self.lens_radius * Vec3::random_in_unit_disk(unigen);
+impl Camera for PerspectiveCamera {
+    fn get_ray(&self, s: f64, t: f64, sampler: &mut SamplerEnum) -> Option<Ray> {
+        let rd = self.lens_radius * Vec3::in_unit_disk_from(sampler.get_2d());
         let offset = self.u * rd.x + self.v * rd.y;
-        Ray {
+        Some(Ray {
             origin: self.origin + offset,
             direction: self.lower_left_corner + s * self.horizontal + t * self.vertical
                 - self.origin - offset,
+            time: shutter_time(self.time0, self.time1, sampler),
+        })
+    }
+}
+
+// Parallel rays from a `height` tall rectangle centred on look_from, so
+// sizes don't shrink with distance
+pub struct OrthographicCamera {
+    lower_left_corner: Vec3,
+    horizontal: Vec3,
+    vertical: Vec3,
+    direction: Vec3,
+    time0: f64,
+    time1: f64,
+}
+
+impl OrthographicCamera {
+    pub fn new(
+        look_from: Vec3,
+        look_at: Vec3,
+        v_up: Vec3,
+        height: f64,
+        aspect_ratio: f64,
+        time0: f64,
+        time1: f64,
+    ) -> OrthographicCamera {
+        let (u, v, w) = basis(look_from, look_at, v_up);
+        let horizontal = aspect_ratio * height * u;
+        let vertical = height * v;
+        OrthographicCamera {
+            lower_left_corner: look_from - horizontal / 2.0 - vertical / 2.0,
+            horizontal,
+            vertical,
+            direction: -w,
+            time0,
+            time1,
         }
     }
 }
+
+impl Camera for OrthographicCamera {
+    fn get_ray(&self, s: f64, t: f64, sampler: &mut SamplerEnum) -> Option<Ray> {
+        Some(Ray {
+            origin: self.lower_left_corner + s * self.horizontal + t * self.vertical,
+            direction: self.direction,
+            time: shutter_time(self.time0, self.time1, sampler),
+        })
+    }
+}
+
+// How a fisheye lens maps the angle from its axis to the distance from the
+// centre of the image
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FisheyeMapping {
+    // Distance proportional to the angle
+    #[default]
+    Equidistant,
+    // Distance proportional to sin(angle / 2), so equal areas of the image
+    // cover equal solid angles
+    Equisolid,
+}
+
+// Circular fisheye with an image circle as tall as the image, covering
+// `fov` degrees across; up to 360 looks all the way round. Outside the
+// circle stays black.
+pub struct FisheyeCamera {
+    origin: Vec3,
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+    aspect_ratio: f64,
+    // Angle from the axis to the edge of the circle, in radians
+    half_fov: f64,
+    mapping: FisheyeMapping,
+    time0: f64,
+    time1: f64,
+}
+
+impl FisheyeCamera {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        look_from: Vec3,
+        look_at: Vec3,
+        v_up: Vec3,
+        fov: f64,
+        mapping: FisheyeMapping,
+        aspect_ratio: f64,
+        time0: f64,
+        time1: f64,
+    ) -> FisheyeCamera {
+        let (u, v, w) = basis(look_from, look_at, v_up);
+        FisheyeCamera {
+            origin: look_from,
+            u,
+            v,
+            w,
+            aspect_ratio,
+            half_fov: fov.to_radians() / 2.0,
+            mapping,
+            time0,
+            time1,
+        }
+    }
+}
+
+impl Camera for FisheyeCamera {
+    fn get_ray(&self, s: f64, t: f64, sampler: &mut SamplerEnum) -> Option<Ray> {
+        // Position relative to the circle, whose radius is 1
+        let x = (2.0 * s - 1.0) * self.aspect_ratio;
+        let y = 2.0 * t - 1.0;
+        let r = (x * x + y * y).sqrt();
+        if r > 1.0 {
+            return None;
+        }
+
+        let theta = match self.mapping {
+            FisheyeMapping::Equidistant => r * self.half_fov,
+            FisheyeMapping::Equisolid => 2.0 * (r * (self.half_fov / 2.0).sin()).asin(),
+        };
+        let phi = y.atan2(x);
+        let direction = theta.sin() * (phi.cos() * self.u + phi.sin() * self.v)
+            - theta.cos() * self.w;
+        Some(Ray {
+            origin: self.origin,
+            direction,
+            time: shutter_time(self.time0, self.time1, sampler),
+        })
+    }
+}
+
+// Full 360 by 180 degree panorama, longitude across and latitude up the
+// image, with look_at in the centre. Best at an aspect ratio of 2.
+pub struct EquirectangularCamera {
+    origin: Vec3,
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+    time0: f64,
+    time1: f64,
+}
+
+impl EquirectangularCamera {
+    pub fn new(
+        look_from: Vec3,
+        look_at: Vec3,
+        v_up: Vec3,
+        time0: f64,
+        time1: f64,
+    ) -> EquirectangularCamera {
+        let (u, v, w) = basis(look_from, look_at, v_up);
+        EquirectangularCamera {
+            origin: look_from,
+            u,
+            v,
+            w,
+            time0,
+            time1,
+        }
+    }
+}
+
+impl Camera for EquirectangularCamera {
+    fn get_ray(&self, s: f64, t: f64, sampler: &mut SamplerEnum) -> Option<Ray> {
+        let longitude = (s - 0.5) * 2.0 * std::f64::consts::PI;
+        let latitude = (t - 0.5) * std::f64::consts::PI;
+        let direction = latitude.cos() * (longitude.sin() * self.u - longitude.cos() * self.w)
+            + latitude.sin() * self.v;
+        Some(Ray {
+            origin: self.origin,
+            direction,
+            time: shutter_time(self.time0, self.time1, sampler),
+        })
+    }
+}
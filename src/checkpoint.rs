@@ -0,0 +1,205 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use bincode::Options;
+use serde::{Deserialize, Serialize};
+
+use crate::framebuffer::Framebuffer;
+use crate::settings::RenderSettings;
+use crate::uniform_wrapper::*;
+use crate::vec3::*;
+
+// Everything a progressive render has accumulated so far: per pixel, the
+// filtered sum of the samples around it, the number taken in it and where
+// its random streams are up to. Continuing the streams makes a render split
+// into passes, or stopped and resumed, come out identical to one done in a
+// single go, except that filters wider than a pixel may round differently.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RenderState {
+    pub width: u32,
+    pub height: u32,
+    // Samples weighted by the filter, and the sum of those weights
+    pub sums: Vec<Vec3>,
+    pub weights: Vec<f64>,
+    // Samples taken in the pixel itself
+    pub counts: Vec<u32>,
+    pub luminance: Vec<LuminanceStats>,
+    // Random stream of each pixel, for the independent sampler
+    pub rngs: Vec<UniRng>,
+}
+
+impl RenderState {
+    pub fn new(settings: &RenderSettings) -> RenderState {
+        let width = settings.image_width;
+        let height = settings.image_height;
+        let seed = settings.seed;
+        let rngs = (0..height)
+            .flat_map(|j| (0..width).map(move |i| (i as u64, j as u64)))
+            .map(|(i, j)| UniGen0_1::seeded(stream_seed(seed, &[i, j, 0])).into_rng())
+            .collect();
+        let pixel_count = width as usize * height as usize;
+        RenderState {
+            width,
+            height,
+            sums: vec![Vec3::zeros(); pixel_count],
+            weights: vec![0.0; pixel_count],
+            counts: vec![0; pixel_count],
+            luminance: vec![LuminanceStats::default(); pixel_count],
+            rngs,
+        }
+    }
+
+    // Fewest samples taken by any pixel
+    pub fn samples_per_pixel(&self) -> u32 {
+        self.counts.iter().copied().min().unwrap_or(0)
+    }
+
+    // Weighted mean of the samples around each pixel; black where none have
+    // been taken
+    pub fn to_framebuffer(&self) -> Framebuffer {
+        Framebuffer {
+            width: self.width,
+            height: self.height,
+            pixels: self
+                .sums
+                .iter()
+                .zip(&self.weights)
+                .map(|(&sum, &weight)| if weight == 0.0 { sum } else { sum / weight })
+                .collect(),
+        }
+    }
+
+    // Each pixel's sample count as a grey level
+    pub fn sample_count_map(&self) -> Framebuffer {
+        Framebuffer {
+            width: self.width,
+            height: self.height,
+            pixels: self
+                .counts
+                .iter()
+                .map(|&count| count as f64 * Vec3::ones())
+                .collect(),
+        }
+    }
+}
+
+// Running mean and variance of the luminance of the samples taken in a
+// pixel, before filtering, updated one sample at a time with Welford's
+// algorithm
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct LuminanceStats {
+    pub mean: f64,
+    // Sum of squared differences from the mean
+    pub m2: f64,
+}
+
+// Luminance below which errors are measured against this instead, so
+// nearly black pixels don't need endless samples
+const ERROR_FLOOR: f64 = 0.01;
+
+impl LuminanceStats {
+    // `count` includes the new sample
+    pub fn add(&mut self, value: f64, count: u32) {
+        let delta = value - self.mean;
+        self.mean += delta / count as f64;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    // Standard error of the mean relative to the mean itself; infinite until
+    // there are two samples to estimate the variance from
+    pub fn relative_error(&self, count: u32) -> f64 {
+        if count < 2 {
+            return f64::INFINITY;
+        }
+        let n = count as f64;
+        let variance = self.m2 / (n - 1.0);
+        (variance / n).sqrt() / self.mean.max(ERROR_FLOOR)
+    }
+}
+
+const CHECKPOINT_MAGIC: [u8; 8] = *b"RTIOWCP4";
+
+// Saved render state, tagged with a hash of the settings it was rendered
+// with so it is only resumed with a matching scene
+#[derive(Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub settings_hash: u64,
+    pub state: RenderState,
+}
+
+// Same layout as Checkpoint, for saving without copying the state
+#[derive(Serialize)]
+struct CheckpointRef<'a> {
+    settings_hash: u64,
+    state: &'a RenderState,
+}
+
+// Fixed-width integers, as bincode::serialize writes them
+fn bincode_options() -> impl Options {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+}
+
+impl Checkpoint {
+    // Writes to a temporary file first, so an interrupted save leaves the
+    // previous checkpoint intact
+    pub fn save(path: impl AsRef<Path>, settings_hash: u64, state: &RenderState) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+        temp_name.push(".tmp");
+        let temp_path = path.with_file_name(temp_name);
+
+        let mut writer = BufWriter::new(File::create(&temp_path)?);
+        writer.write_all(&CHECKPOINT_MAGIC)?;
+        let checkpoint = CheckpointRef {
+            settings_hash,
+            state,
+        };
+        bincode_options()
+            .serialize_into(&mut writer, &checkpoint)
+            .map_err(io::Error::other)?;
+        writer.flush()?;
+        drop(writer);
+        fs::rename(temp_path, path)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Checkpoint> {
+        let file = File::open(path)?;
+        let file_len = file.metadata()?.len();
+        let mut reader = BufReader::new(file);
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if magic != CHECKPOINT_MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a render checkpoint",
+            ));
+        }
+        // Limited to the file's size, so a corrupt length can't ask for more
+        let checkpoint: Checkpoint = bincode_options()
+            .with_limit(file_len)
+            .deserialize_from(reader)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        let state = &checkpoint.state;
+        let pixel_count = (state.width as usize)
+            .checked_mul(state.height as usize)
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, "checkpoint image is too large")
+            })?;
+        if state.sums.len() != pixel_count
+            || state.weights.len() != pixel_count
+            || state.counts.len() != pixel_count
+            || state.luminance.len() != pixel_count
+            || state.rngs.len() != pixel_count
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "checkpoint doesn't match its image size",
+            ));
+        }
+        Ok(checkpoint)
+    }
+}
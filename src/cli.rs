@@ -0,0 +1,518 @@
+use std::path::{Path, PathBuf};
+
+use clap::{Parser, ValueEnum};
+use image::ImageFormat;
+
+use rtiow_rust::presets::PRESET_NAMES;
+use rtiow_rust::checkpoint::RenderState;
+use rtiow_rust::camera::FisheyeMapping;
+use rtiow_rust::filter::Filter;
+use rtiow_rust::sampler::SamplerKind;
+use rtiow_rust::scene::{BackgroundDescription, ProjectionDescription};
+use rtiow_rust::settings::AdaptiveSettings;
+use rtiow_rust::tiles::TileOrder;
+use rtiow_rust::{Framebuffer, SceneDescription, ToneMapOperator, ToneMapSettings, Vec3};
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum OutputFormat {
+    Bmp,
+    Png,
+    Jpeg,
+    Tiff,
+    Tga,
+    // Linear float formats, written straight from the framebuffer
+    Exr,
+    Hdr,
+    Pfm,
+}
+
+impl OutputFormat {
+    pub fn from_path(path: &Path) -> Option<OutputFormat> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "bmp" => Some(OutputFormat::Bmp),
+            "png" => Some(OutputFormat::Png),
+            "jpg" | "jpeg" => Some(OutputFormat::Jpeg),
+            "tif" | "tiff" => Some(OutputFormat::Tiff),
+            "tga" => Some(OutputFormat::Tga),
+            "exr" => Some(OutputFormat::Exr),
+            "hdr" => Some(OutputFormat::Hdr),
+            "pfm" => Some(OutputFormat::Pfm),
+            _ => None,
+        }
+    }
+
+    // Float formats keep linear radiance, 8-bit ones are tone mapped first
+    pub fn save(
+        self,
+        framebuffer: &Framebuffer,
+        tone_map: &ToneMapSettings,
+        path: &Path,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let ldr_format = match self {
+            OutputFormat::Bmp => ImageFormat::Bmp,
+            OutputFormat::Png => ImageFormat::Png,
+            OutputFormat::Jpeg => ImageFormat::Jpeg,
+            OutputFormat::Tiff => ImageFormat::Tiff,
+            OutputFormat::Tga => ImageFormat::Tga,
+            OutputFormat::Exr => return Ok(framebuffer.save_exr(path)?),
+            OutputFormat::Hdr => return Ok(framebuffer.save_hdr(path)?),
+            OutputFormat::Pfm => return Ok(framebuffer.save_pfm(path)?),
+        };
+        framebuffer
+            .to_rgb_image(tone_map)
+            .save_with_format(path, ldr_format)?;
+        Ok(())
+    }
+
+    // Sample counts from an adaptive render
+    pub fn save_sample_map(
+        self,
+        state: &RenderState,
+        path: &Path,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut map = state.sample_count_map();
+        if !matches!(self, OutputFormat::Exr | OutputFormat::Hdr | OutputFormat::Pfm) {
+            let max_count = state.counts.iter().copied().max().unwrap_or(0).max(1);
+            for pixel in &mut map.pixels {
+                *pixel = *pixel / max_count as f64;
+            }
+        }
+        self.save(&map, &ToneMapSettings::default(), path)
+    }
+}
+
+const DEFAULT_WHITE_POINT: f64 = 4.0;
+
+// Pass size for checkpointed renders that don't set one
+const CHECKPOINT_PASS_SPP: u32 = 16;
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum SamplerArg {
+    Independent,
+    Stratified,
+    Halton,
+    Sobol,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ProjectionArg {
+    Perspective,
+    Orthographic,
+    Fisheye,
+    Equirectangular,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum FisheyeMappingArg {
+    Equidistant,
+    Equisolid,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum FilterArg {
+    Box,
+    Tent,
+    Gaussian,
+    Mitchell,
+    Lanczos,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum TileOrderArg {
+    Scanline,
+    Spiral,
+    Hilbert,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ToneMapArg {
+    Clamp,
+    Reinhard,
+    ExtendedReinhard,
+    Aces,
+    Hable,
+}
+
+/// Ray Tracing in One Weekend renderer.
+///
+/// Renders a scene file or a built-in preset. Options given here override
+/// the values in the scene.
+#[derive(Debug, Parser)]
+#[command(version)]
+pub struct Cli {
+    /// Scene description file (JSON)
+    #[arg(long, conflicts_with = "preset")]
+    pub scene: Option<PathBuf>,
+
+    /// Built-in scene to render when no scene file is given
+    #[arg(long, default_value = "random", value_parser = clap::builder::PossibleValuesParser::new(PRESET_NAMES))]
+    pub preset: String,
+
+    /// Write the scene (after overrides) to this file instead of rendering
+    #[arg(long, value_name = "FILE")]
+    pub export: Option<PathBuf>,
+
+    /// Output image path; repeat to write several files, e.g. -o image.png -o image.exr
+    #[arg(short, long, default_value = "image.bmp")]
+    pub output: Vec<PathBuf>,
+
+    /// Output image format, inferred from each output's extension by default
+    #[arg(long, value_enum)]
+    pub format: Option<OutputFormat>,
+
+    /// Tone mapping operator for 8-bit outputs
+    #[arg(long, value_enum)]
+    pub tonemap: Option<ToneMapArg>,
+
+    /// Luminance mapped to white by the extended Reinhard operator (default 4)
+    #[arg(long)]
+    pub white_point: Option<f64>,
+
+    /// Exposure compensation in stops (EV) for 8-bit outputs
+    #[arg(long, allow_hyphen_values = true)]
+    pub exposure: Option<f64>,
+
+    /// Equirectangular .hdr or .exr image to light the scene with
+    #[arg(long, value_name = "FILE")]
+    pub environment: Option<PathBuf>,
+
+    /// Environment map rotation about the up axis, in degrees
+    #[arg(long, allow_hyphen_values = true)]
+    pub env_rotation: Option<f64>,
+
+    /// Multiplier for the environment map's radiance
+    #[arg(long)]
+    pub env_intensity: Option<f64>,
+
+    /// Image width in pixels
+    #[arg(long)]
+    pub width: Option<u32>,
+
+    /// Image height in pixels; derived from the aspect ratio if not given
+    #[arg(long)]
+    pub height: Option<u32>,
+
+    /// Image aspect ratio (width / height)
+    #[arg(long)]
+    pub aspect: Option<f64>,
+
+    /// Samples per pixel
+    #[arg(long)]
+    pub spp: Option<u32>,
+
+    /// Maximum number of bounces per path
+    #[arg(long)]
+    pub max_depth: Option<u32>,
+
+    /// Bounces before Russian roulette may terminate a path
+    #[arg(long)]
+    pub roulette_depth: Option<u32>,
+
+    /// Render progressively, adding this many samples per pixel in each pass
+    /// and writing the outputs after every pass
+    #[arg(long)]
+    pub pass_spp: Option<u32>,
+
+    /// Save the render's progress to this file after every pass; passes are
+    /// 16 samples per pixel unless --pass-spp is given
+    #[arg(long, value_name = "FILE")]
+    pub checkpoint: Option<PathBuf>,
+
+    /// Continue a render from a checkpoint saved with the same scene
+    #[arg(long, value_name = "FILE")]
+    pub resume: Option<PathBuf>,
+
+    /// How the random numbers for each sample are chosen
+    #[arg(long, value_enum)]
+    pub sampler: Option<SamplerArg>,
+
+    /// Pixel reconstruction filter
+    #[arg(long, value_enum)]
+    pub filter: Option<FilterArg>,
+
+    /// Filter radius in pixels, e.g. 0.5 for a box covering one pixel
+    #[arg(long)]
+    pub filter_radius: Option<f64>,
+
+    /// Keep sampling pixels whose relative error is above this, e.g. 0.01
+    #[arg(long)]
+    pub adaptive_threshold: Option<f64>,
+
+    /// Most samples per pixel adaptive sampling may take
+    #[arg(long)]
+    pub max_spp: Option<u32>,
+
+    /// Write each pixel's sample count to this image; float formats store
+    /// the counts, others scale them to the largest
+    #[arg(long, value_name = "FILE")]
+    pub sample_map: Option<PathBuf>,
+
+    /// Edge length in pixels of the tiles handed to render threads
+    #[arg(long)]
+    pub tile_size: Option<u32>,
+
+    /// Order in which tiles are rendered
+    #[arg(long, value_enum)]
+    pub tile_order: Option<TileOrderArg>,
+
+    /// Number of render threads (defaults to one per core)
+    #[arg(long)]
+    pub threads: Option<usize>,
+
+    /// Seed for randomly generated presets and for the render's random streams
+    /// (presets default to 0)
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Camera projection
+    #[arg(long, value_enum)]
+    pub projection: Option<ProjectionArg>,
+
+    /// Height of the orthographic view in world units
+    #[arg(long)]
+    pub ortho_height: Option<f64>,
+
+    /// How the fisheye maps angles onto the image
+    #[arg(long, value_enum)]
+    pub fisheye_mapping: Option<FisheyeMappingArg>,
+
+    /// Camera position as x,y,z
+    #[arg(long, value_name = "X,Y,Z", allow_hyphen_values = true)]
+    pub look_from: Option<Vec3>,
+
+    /// Point the camera looks at as x,y,z
+    #[arg(long, value_name = "X,Y,Z", allow_hyphen_values = true)]
+    pub look_at: Option<Vec3>,
+
+    /// Vertical field of view in degrees; for a fisheye, the angle across
+    /// its image circle
+    #[arg(long)]
+    pub fov: Option<f64>,
+
+    /// Lens aperture; 0 disables depth of field
+    #[arg(long)]
+    pub aperture: Option<f64>,
+
+    /// Distance to the plane in focus
+    #[arg(long)]
+    pub focus_dist: Option<f64>,
+
+    /// Time the shutter opens, for motion blur
+    #[arg(long, allow_hyphen_values = true)]
+    pub shutter_open: Option<f64>,
+
+    /// Time the shutter closes, for motion blur
+    #[arg(long, allow_hyphen_values = true)]
+    pub shutter_close: Option<f64>,
+}
+
+impl Cli {
+    // Every output path with the format it will be written in
+    pub fn outputs(&self) -> Result<Vec<(PathBuf, OutputFormat)>, String> {
+        if self.format.is_some() && self.output.len() > 1 {
+            return Err("--format can only be used with a single output".to_string());
+        }
+        self.output
+            .iter()
+            .map(|path| {
+                self.format
+                    .or_else(|| OutputFormat::from_path(path))
+                    .map(|format| (path.clone(), format))
+                    .ok_or_else(|| {
+                        format!(
+                            "can't infer an image format from '{}', use --format",
+                            path.display()
+                        )
+                    })
+            })
+            .collect()
+    }
+
+    pub fn sample_map_output(&self) -> Result<Option<(PathBuf, OutputFormat)>, String> {
+        self.sample_map
+            .as_ref()
+            .map(|path| {
+                OutputFormat::from_path(path)
+                    .map(|format| (path.clone(), format))
+                    .ok_or_else(|| {
+                        format!("can't infer an image format from '{}'", path.display())
+                    })
+            })
+            .transpose()
+    }
+
+    // Fails on options that don't apply to the scene they'd change
+    pub fn apply_overrides(&self, description: &mut SceneDescription) -> Result<(), String> {
+        let image = &mut description.image;
+        if let Some(width) = self.width {
+            // Keep the scene's proportions when only the width changes
+            if let (Some(height), None, None) = (image.height, self.height, self.aspect) {
+                image.aspect_ratio = Some(image.width as f64 / height as f64);
+                image.height = None;
+            }
+            image.width = width;
+        }
+        if let Some(aspect) = self.aspect {
+            image.aspect_ratio = Some(aspect);
+            image.height = None;
+        }
+        if let Some(height) = self.height {
+            image.height = Some(height);
+        }
+        if let Some(spp) = self.spp {
+            image.samples_per_pixel = spp;
+        }
+        if let Some(pass_spp) = self.pass_spp {
+            image.samples_per_pass = Some(pass_spp);
+        } else if self.checkpoint.is_some() && image.samples_per_pass.is_none() {
+            // A single pass would only be saved once it's done
+            image.samples_per_pass = Some(CHECKPOINT_PASS_SPP);
+        }
+        if let Some(sampler) = self.sampler {
+            image.sampler = match sampler {
+                SamplerArg::Independent => SamplerKind::Independent,
+                SamplerArg::Stratified => SamplerKind::Stratified,
+                SamplerArg::Halton => SamplerKind::Halton,
+                SamplerArg::Sobol => SamplerKind::Sobol,
+            };
+        }
+        if let Some(filter) = self.filter {
+            image.filter = match filter {
+                FilterArg::Box => Filter::box_filter(),
+                FilterArg::Tent => Filter::tent(),
+                FilterArg::Gaussian => Filter::gaussian(),
+                FilterArg::Mitchell => Filter::mitchell(),
+                FilterArg::Lanczos => Filter::lanczos(),
+            };
+        }
+        if let Some(radius) = self.filter_radius {
+            image.filter.set_radius(radius);
+        }
+        if self.adaptive_threshold.is_some() || self.max_spp.is_some() {
+            let adaptive = image.adaptive.get_or_insert_with(AdaptiveSettings::default);
+            if let Some(threshold) = self.adaptive_threshold {
+                adaptive.threshold = threshold;
+            }
+            if let Some(max_spp) = self.max_spp {
+                adaptive.max_samples_per_pixel = max_spp;
+            }
+        }
+        if let Some(max_depth) = self.max_depth {
+            image.max_depth = max_depth;
+        }
+        if let Some(roulette_depth) = self.roulette_depth {
+            image.roulette_depth = roulette_depth;
+        }
+        if let Some(seed) = self.seed {
+            image.seed = seed;
+        }
+        if let Some(tile_size) = self.tile_size {
+            image.tile_size = tile_size;
+        }
+        if let Some(tile_order) = self.tile_order {
+            image.tile_order = match tile_order {
+                TileOrderArg::Scanline => TileOrder::Scanline,
+                TileOrderArg::Spiral => TileOrder::Spiral,
+                TileOrderArg::Hilbert => TileOrder::Hilbert,
+            };
+        }
+        if let Some(tonemap) = self.tonemap {
+            image.tone_map.operator = match tonemap {
+                ToneMapArg::Clamp => ToneMapOperator::Clamp,
+                ToneMapArg::Reinhard => ToneMapOperator::Reinhard,
+                ToneMapArg::ExtendedReinhard => ToneMapOperator::ExtendedReinhard {
+                    white_point: self.white_point.unwrap_or(DEFAULT_WHITE_POINT),
+                },
+                ToneMapArg::Aces => ToneMapOperator::Aces,
+                ToneMapArg::Hable => ToneMapOperator::Hable,
+            };
+        }
+        if let Some(white_point) = self.white_point {
+            match &mut image.tone_map.operator {
+                ToneMapOperator::ExtendedReinhard {
+                    white_point: scene_white_point,
+                } => *scene_white_point = white_point,
+                _ => {
+                    return Err(
+                        "--white-point needs the extended-reinhard tone map".to_string()
+                    )
+                }
+            }
+        }
+        if let Some(exposure) = self.exposure {
+            image.tone_map.exposure = exposure;
+        }
+
+        if let Some(path) = &self.environment {
+            // Scene paths are relative to the scene file, this one to the working directory
+            description.background = BackgroundDescription::Environment {
+                path: std::path::absolute(path).unwrap_or_else(|_| path.clone()),
+                rotation: 0.0,
+                intensity: 1.0,
+            };
+        }
+        if let BackgroundDescription::Environment {
+            rotation, intensity, ..
+        } = &mut description.background
+        {
+            if let Some(env_rotation) = self.env_rotation {
+                *rotation = env_rotation;
+            }
+            if let Some(env_intensity) = self.env_intensity {
+                *intensity = env_intensity;
+            }
+        }
+
+        let camera = &mut description.camera;
+        if let Some(projection) = self.projection {
+            camera.projection = match projection {
+                ProjectionArg::Perspective => ProjectionDescription::Perspective,
+                ProjectionArg::Orthographic => ProjectionDescription::Orthographic { height: None },
+                ProjectionArg::Fisheye => ProjectionDescription::Fisheye {
+                    mapping: FisheyeMapping::default(),
+                },
+                ProjectionArg::Equirectangular => ProjectionDescription::Equirectangular,
+            };
+        }
+        if let Some(ortho_height) = self.ortho_height {
+            match &mut camera.projection {
+                ProjectionDescription::Orthographic { height } => *height = Some(ortho_height),
+                _ => return Err("--ortho-height needs the orthographic projection".to_string()),
+            }
+        }
+        if let Some(fisheye_mapping) = self.fisheye_mapping {
+            match &mut camera.projection {
+                ProjectionDescription::Fisheye { mapping } => {
+                    *mapping = match fisheye_mapping {
+                        FisheyeMappingArg::Equidistant => FisheyeMapping::Equidistant,
+                        FisheyeMappingArg::Equisolid => FisheyeMapping::Equisolid,
+                    }
+                }
+                _ => return Err("--fisheye-mapping needs the fisheye projection".to_string()),
+            }
+        }
+        if let Some(look_from) = self.look_from {
+            camera.look_from = look_from;
+        }
+        if let Some(look_at) = self.look_at {
+            camera.look_at = look_at;
+        }
+        if let Some(fov) = self.fov {
+            camera.v_fov = fov;
+        }
+        if let Some(aperture) = self.aperture {
+            camera.aperture = aperture;
+        }
+        if let Some(focus_dist) = self.focus_dist {
+            camera.focus_dist = Some(focus_dist);
+        }
+        if let Some(shutter_open) = self.shutter_open {
+            camera.shutter_open = shutter_open;
+        }
+        if let Some(shutter_close) = self.shutter_close {
+            camera.shutter_close = shutter_close;
+        }
+        Ok(())
+    }
+}
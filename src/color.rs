@@ -1,63 +1,170 @@
-use crate::hit::Hit;
+use crate::background::Background;
+use crate::hit::{Hit, HitRecord};
+use crate::lights::LightList;
+use crate::medium::MediumList;
 use crate::ray::*;
-use crate::uniform_wrapper::*;
+use crate::sampler::{Sampler, SamplerEnum};
 use crate::vec3::*;
 use image::Rgb;
-use rand::distributions::Uniform;
-use rand::prelude::ThreadRng;
 use crate::material::Material;
 
 pub trait IntoColor {
     fn into_color(self) -> Rgb<u8>;
 }
 
+// Quantizes an already encoded [0, 1] color, clamping anything outside
+fn quantize(c: f64) -> u8 {
+    (255.0 * c.clamp(0.0, 1.0)).round() as u8
+}
+
 impl IntoColor for Vec3 {
     fn into_color(self) -> Rgb<u8> {
-        Rgb([
-            (255.0 * self.x) as u8,
-            (255.0 * self.y) as u8,
-            (255.0 * self.z) as u8,
-        ])
+        Rgb([quantize(self.x), quantize(self.y), quantize(self.z)])
     }
 }
 
 impl IntoColor for [f64; 3] {
     fn into_color(self) -> Rgb<u8> {
-        Rgb([
-            (255.0 * self[0]) as u8,
-            (255.0 * self[1]) as u8,
-            (255.0 * self[2]) as u8,
-        ])
+        Rgb([quantize(self[0]), quantize(self[1]), quantize(self[2])])
+    }
+}
+
+// Power heuristic weight (beta = 2) for a sample drawn with density `pdf`
+// when `other_pdf` is the density of the competing strategy
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let pdf_squared = pdf * pdf;
+    let sum = pdf_squared + other_pdf * other_pdf;
+    if sum <= 0.0 {
+        0.0
+    } else {
+        pdf_squared / sum
     }
 }
 
+// Russian roulette keeps a path with probability equal to its throughput,
+// but never more than this, so even bright paths can end
+const MAX_SURVIVAL_PROBABILITY: f64 = 0.95;
+
+// Follows one path from the camera, carrying its throughput iteratively.
+// Direct light is estimated both by sampling the lights and by following the
+// scattered ray, with the two combined by multiple importance sampling.
+// After `roulette_depth` bounces paths are ended at random with a probability
+// based on their throughput, and survivors are reweighted so the estimate
+// stays unbiased. Along each segment the ray may first scatter inside a
+// participating medium.
+#[allow(clippy::too_many_arguments)]
 pub fn ray_color_vec(
     r: &Ray,
     world: &impl Hit,
-    unigen0_1: &mut UniGen0_1,
-    unigen_neg1_1: &mut UniGenNeg1_1,
-    depth: u32,
+    lights: &LightList,
+    media: &MediumList,
+    background: &Background,
+    sampler: &mut SamplerEnum,
+    max_depth: u32,
+    roulette_depth: u32,
 ) -> Vec3 {
-    if depth == 0 {
-        return Vec3::zeros();
-    }
+    let mut radiance = Vec3::zeros();
+    let mut throughput = Vec3::ones();
+    let mut ray = *r;
+    // Density the current ray was scattered with, or None for camera rays
+    // and specular bounces, which light sampling can't reproduce
+    let mut bsdf_pdf: Option<f64> = None;
+
+    for depth in 0..max_depth {
+        let surface = world.hit(&ray, 0.001, f64::INFINITY);
+        let t_surface = surface.as_ref().map_or(f64::INFINITY, |rec| rec.t);
+        let medium_event = media.sample_interaction(&ray, 0.001, t_surface, sampler);
+        let Some(rec) = medium_event.or(surface) else {
+            // An environment map may also have been reached by light sampling
+            let mut escaped = background.color(&ray);
+            if let Some(bsdf_pdf) = bsdf_pdf {
+                let light_pdf = lights.pdf_value(ray.origin, ray.direction);
+                escaped *= power_heuristic(bsdf_pdf, light_pdf);
+            }
+            radiance += throughput * escaped;
+            break;
+        };
+
+        let mut emitted = rec.mat_ref.emitted(&rec);
+        if let Some(bsdf_pdf) = bsdf_pdf {
+            if !emitted.near_zero() {
+                let light_pdf = lights.pdf_value(ray.origin, ray.direction);
+                emitted *= power_heuristic(bsdf_pdf, light_pdf);
+            }
+        }
+        radiance += throughput * emitted;
 
-    let option_rec = world.hit(r, 0.001, f64::INFINITY);
-    if let Some(rec) = option_rec {
-        let scatter_result_option = rec.mat_ref.scatter(r, &rec, unigen0_1, unigen_neg1_1);
-        if let Some(scatter_result) = scatter_result_option {
-            scatter_result.attenuation * ray_color_vec(&scatter_result.ray, world, unigen0_1, unigen_neg1_1, depth - 1)
+        let scatter_result_option = rec.mat_ref.scatter(&ray, &rec, sampler);
+        let specular = scatter_result_option
+            .as_ref()
+            .is_some_and(|scatter_result| scatter_result.specular);
+        if !specular && !lights.is_empty() {
+            radiance += throughput * sample_lights(&ray, &rec, world, lights, media, background, sampler);
+        }
+
+        let Some(scatter_result) = scatter_result_option else {
+            break;
+        };
+        throughput = throughput * scatter_result.attenuation;
+        bsdf_pdf = if scatter_result.specular {
+            None
         } else {
-            Vec3::zeros()
+            Some(scatter_result.pdf)
+        };
+        ray = scatter_result.ray;
+
+        if depth + 1 >= roulette_depth {
+            let survival_probability = throughput
+                .x
+                .max(throughput.y)
+                .max(throughput.z)
+                .min(MAX_SURVIVAL_PROBABILITY);
+            if sampler.get_1d() >= survival_probability {
+                break;
+            }
+            throughput = throughput / survival_probability;
         }
-    } else {
-            let unit_direction = r.direction.unit_vec();
-            let t = 0.5 * (unit_direction.y + 1.0);
-            (1.0 - t) * Vec3::ones()
-                + t * Vec3 {
-                    x: 0.5,
-                    y: 0.7,
-                    z: 1.0,
-                }
     }
+
+    radiance
+}
+
+// Direct lighting at `rec` from one shadow ray towards a randomly chosen
+// light, weighted against the chance of scattering in the same direction
+fn sample_lights(
+    r: &Ray,
+    rec: &HitRecord,
+    world: &impl Hit,
+    lights: &LightList,
+    media: &MediumList,
+    background: &Background,
+    sampler: &mut SamplerEnum,
+) -> Vec3 {
+    let direction = lights.random_direction(rec.p, sampler);
+    let light_pdf = lights.pdf_value(rec.p, direction);
+    if light_pdf <= 0.0 {
+        return Vec3::zeros();
+    }
+
+    let bsdf_cos = rec.mat_ref.eval(r, rec, direction);
+    if bsdf_cos.near_zero() {
+        return Vec3::zeros();
+    }
+
+    let shadow_ray = Ray {
+        origin: rec.p,
+        direction,
+        time: r.time,
+    };
+    // Shadow rays only escape when the environment map was sampled
+    let (incoming, t_light) = match world.hit(&shadow_ray, 0.001, f64::INFINITY) {
+        Some(light_rec) => (light_rec.mat_ref.emitted(&light_rec), light_rec.t),
+        None => (background.color(&shadow_ray), f64::INFINITY),
+    };
+    if incoming.near_zero() {
+        return Vec3::zeros();
+    }
+    let incoming = incoming * media.transmittance(&shadow_ray, 0.001, t_light, sampler);
+    let bsdf_pdf = rec.mat_ref.scattering_pdf(r, rec, direction);
+    bsdf_cos * incoming * (power_heuristic(light_pdf, bsdf_pdf) / light_pdf)
 }
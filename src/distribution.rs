@@ -0,0 +1,116 @@
+// Piecewise-constant distribution over [0, 1) with one segment per value
+pub struct Distribution1D {
+    func: Vec<f64>,
+    cdf: Vec<f64>,
+    integral: f64,
+}
+
+impl Distribution1D {
+    // Negative values are treated as zero. If every value is zero the
+    // distribution falls back to uniform.
+    pub fn new(func: Vec<f64>) -> Distribution1D {
+        let n = func.len();
+        assert!(n > 0, "distribution needs at least one value");
+        let func: Vec<f64> = func.into_iter().map(|value| value.max(0.0)).collect();
+
+        let mut cdf = vec![0.0; n + 1];
+        for i in 0..n {
+            cdf[i + 1] = cdf[i] + func[i] / n as f64;
+        }
+        let integral = cdf[n];
+        for (i, value) in cdf.iter_mut().enumerate() {
+            *value = if integral > 0.0 {
+                *value / integral
+            } else {
+                i as f64 / n as f64
+            };
+        }
+
+        Distribution1D {
+            func,
+            cdf,
+            integral,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.func.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.func.is_empty()
+    }
+
+    // Integral of the function over [0, 1)
+    pub fn integral(&self) -> f64 {
+        self.integral
+    }
+
+    // Maps a uniform `u` to a point in [0, 1), returning it with its density
+    // and the index of the segment it fell in
+    pub fn sample(&self, u: f64) -> (f64, f64, usize) {
+        let n = self.len();
+        let offset = (self.cdf.partition_point(|&c| c <= u).max(1) - 1).min(n - 1);
+
+        let width = self.cdf[offset + 1] - self.cdf[offset];
+        let du = if width > 0.0 {
+            (u - self.cdf[offset]) / width
+        } else {
+            0.0
+        };
+        let x = ((offset as f64 + du) / n as f64).min(1.0 - f64::EPSILON);
+
+        (x, self.segment_pdf(offset), offset)
+    }
+
+    pub fn pdf(&self, x: f64) -> f64 {
+        self.segment_pdf(self.segment(x))
+    }
+
+    fn segment(&self, x: f64) -> usize {
+        ((x * self.len() as f64) as usize).min(self.len() - 1)
+    }
+
+    fn segment_pdf(&self, index: usize) -> f64 {
+        if self.integral > 0.0 {
+            self.func[index] / self.integral
+        } else {
+            1.0
+        }
+    }
+}
+
+// Piecewise-constant distribution over [0, 1)^2, sampled by picking a row
+// from the marginal distribution and then a column within that row
+pub struct Distribution2D {
+    conditional: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    // `func` is row-major with `width` values per row
+    pub fn new(func: &[f64], width: usize, height: usize) -> Distribution2D {
+        assert_eq!(func.len(), width * height);
+        let conditional: Vec<Distribution1D> = func
+            .chunks(width)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(conditional.iter().map(Distribution1D::integral).collect());
+        Distribution2D {
+            conditional,
+            marginal,
+        }
+    }
+
+    // Returns the sampled (u, v) and its density
+    pub fn sample(&self, u0: f64, u1: f64) -> ([f64; 2], f64) {
+        let (v, pdf_v, row) = self.marginal.sample(u1);
+        let (u, pdf_u, _) = self.conditional[row].sample(u0);
+        ([u, v], pdf_u * pdf_v)
+    }
+
+    pub fn pdf(&self, u: f64, v: f64) -> f64 {
+        let row = self.marginal.segment(v);
+        self.conditional[row].pdf(u) * self.marginal.pdf(v)
+    }
+}
@@ -0,0 +1,110 @@
+use std::f64::consts::PI;
+use std::io;
+use std::path::Path;
+
+use crate::distribution::Distribution2D;
+use crate::framebuffer::Framebuffer;
+use crate::tonemap::luminance;
+use crate::sampler::{Sampler, SamplerEnum};
+use crate::vec3::*;
+
+// Equirectangular environment map used as the background and as a light.
+// The top row of the image is straight up (+y), the center column looks
+// along -z and u increases towards +x.
+pub struct EnvironmentMap {
+    image: Framebuffer,
+    // Radians about the +y axis
+    rotation: f64,
+    intensity: f64,
+    // Proportional to each pixel's luminance times its solid angle
+    distribution: Distribution2D,
+}
+
+impl EnvironmentMap {
+    pub fn new(image: Framebuffer, rotation_degrees: f64, intensity: f64) -> EnvironmentMap {
+        let width = image.width as usize;
+        let height = image.height as usize;
+
+        let mut func = Vec::with_capacity(width * height);
+        for y in 0..image.height {
+            // Rows near the poles cover less solid angle
+            let sin_theta = (PI * (y as f64 + 0.5) / height as f64).sin();
+            for x in 0..image.width {
+                func.push(luminance(image.get_pixel(x, y)) * sin_theta);
+            }
+        }
+        let distribution = Distribution2D::new(&func, width, height);
+
+        EnvironmentMap {
+            image,
+            rotation: rotation_degrees.to_radians(),
+            intensity,
+            distribution,
+        }
+    }
+
+    // Loads an .hdr or .exr image. 8-bit images also load but can't hold
+    // highlights brighter than white.
+    pub fn load(
+        path: impl AsRef<Path>,
+        rotation_degrees: f64,
+        intensity: f64,
+    ) -> io::Result<EnvironmentMap> {
+        let image = Framebuffer::load(path)?;
+        if image.width == 0 || image.height == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "empty image"));
+        }
+        Ok(EnvironmentMap::new(image, rotation_degrees, intensity))
+    }
+
+    pub fn radiance(&self, direction: Vec3) -> Vec3 {
+        let [u, v] = self.direction_to_uv(direction);
+        let x = ((u * self.image.width as f64) as u32).min(self.image.width - 1);
+        let y = ((v * self.image.height as f64) as u32).min(self.image.height - 1);
+        self.intensity * self.image.get_pixel(x, y)
+    }
+
+    // Solid angle density of random_direction generating `direction`
+    pub fn pdf_value(&self, direction: Vec3) -> f64 {
+        let [u, v] = self.direction_to_uv(direction);
+        let sin_theta = (PI * v).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        self.distribution.pdf(u, v) / (2.0 * PI * PI * sin_theta)
+    }
+
+    // Direction chosen in proportion to the map's luminance
+    pub fn random_direction(&self, sampler: &mut SamplerEnum) -> Vec3 {
+        let (u0, u1) = sampler.get_2d();
+        let ([u, v], _) = self.distribution.sample(u0, u1);
+        self.uv_to_direction(u, v)
+    }
+
+    fn direction_to_uv(&self, direction: Vec3) -> [f64; 2] {
+        let d = rotate_y(direction.unit_vec(), -self.rotation);
+        let phi = d.x.atan2(-d.z);
+        let theta = d.y.clamp(-1.0, 1.0).acos();
+        [0.5 + phi / (2.0 * PI), theta / PI]
+    }
+
+    fn uv_to_direction(&self, u: f64, v: f64) -> Vec3 {
+        let phi = 2.0 * PI * (u - 0.5);
+        let theta = PI * v;
+        let local = Vec3 {
+            x: theta.sin() * phi.sin(),
+            y: theta.cos(),
+            z: -theta.sin() * phi.cos(),
+        };
+        rotate_y(local, self.rotation)
+    }
+}
+
+fn rotate_y(v: Vec3, angle: f64) -> Vec3 {
+    let (sin, cos) = angle.sin_cos();
+    Vec3 {
+        x: cos * v.x + sin * v.z,
+        y: v.y,
+        z: -sin * v.x + cos * v.z,
+    }
+}
@@ -0,0 +1,259 @@
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
+fn default_box_radius() -> f64 {
+    0.5
+}
+
+fn default_tent_radius() -> f64 {
+    1.0
+}
+
+fn default_gaussian_radius() -> f64 {
+    1.5
+}
+
+fn default_gaussian_sigma() -> f64 {
+    0.5
+}
+
+fn default_mitchell_radius() -> f64 {
+    2.0
+}
+
+fn default_mitchell_b() -> f64 {
+    1.0 / 3.0
+}
+
+fn default_mitchell_c() -> f64 {
+    1.0 / 3.0
+}
+
+fn default_lanczos_radius() -> f64 {
+    3.0
+}
+
+// Weights each sample by its distance from a pixel's centre when
+// reconstructing the image. Samples count towards every pixel whose centre
+// is within `radius` pixels in x and y, so anything wider than the default
+// box blends neighbouring pixels. Mitchell and Lanczos have negative lobes,
+// which sharpen edges but can ring around very bright ones.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Filter {
+    // Equal weight for every sample in the pixel, a plain average
+    Box {
+        #[serde(default = "default_box_radius")]
+        radius: f64,
+    },
+    // Falls off linearly to zero at the radius
+    Tent {
+        #[serde(default = "default_tent_radius")]
+        radius: f64,
+    },
+    // Gaussian with standard deviation `sigma`, shifted down to reach zero
+    // at the radius
+    Gaussian {
+        #[serde(default = "default_gaussian_radius")]
+        radius: f64,
+        #[serde(default = "default_gaussian_sigma")]
+        sigma: f64,
+    },
+    // Mitchell-Netravali cubic; the defaults b = c = 1/3 are the ones the
+    // paper recommends
+    Mitchell {
+        #[serde(default = "default_mitchell_radius")]
+        radius: f64,
+        #[serde(default = "default_mitchell_b")]
+        b: f64,
+        #[serde(default = "default_mitchell_c")]
+        c: f64,
+    },
+    // Sinc windowed by a wider sinc that reaches zero at the radius
+    Lanczos {
+        #[serde(default = "default_lanczos_radius")]
+        radius: f64,
+    },
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Filter::box_filter()
+    }
+}
+
+// The filters with their default parameters
+impl Filter {
+    pub fn box_filter() -> Filter {
+        Filter::Box {
+            radius: default_box_radius(),
+        }
+    }
+
+    pub fn tent() -> Filter {
+        Filter::Tent {
+            radius: default_tent_radius(),
+        }
+    }
+
+    pub fn gaussian() -> Filter {
+        Filter::Gaussian {
+            radius: default_gaussian_radius(),
+            sigma: default_gaussian_sigma(),
+        }
+    }
+
+    pub fn mitchell() -> Filter {
+        Filter::Mitchell {
+            radius: default_mitchell_radius(),
+            b: default_mitchell_b(),
+            c: default_mitchell_c(),
+        }
+    }
+
+    pub fn lanczos() -> Filter {
+        Filter::Lanczos {
+            radius: default_lanczos_radius(),
+        }
+    }
+}
+
+fn gaussian(x: f64, sigma: f64) -> f64 {
+    (-x * x / (2.0 * sigma * sigma)).exp()
+}
+
+// x in [0, 2]
+fn mitchell(x: f64, b: f64, c: f64) -> f64 {
+    let polynomial = if x > 1.0 {
+        (-b - 6.0 * c) * x * x * x + (6.0 * b + 30.0 * c) * x * x + (-12.0 * b - 48.0 * c) * x
+            + (8.0 * b + 24.0 * c)
+    } else {
+        (12.0 - 9.0 * b - 6.0 * c) * x * x * x + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+            + (6.0 - 2.0 * b)
+    };
+    polynomial / 6.0
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-5 {
+        return 1.0;
+    }
+    let x = std::f64::consts::PI * x;
+    x.sin() / x
+}
+
+impl Filter {
+    pub fn radius(&self) -> f64 {
+        match *self {
+            Filter::Box { radius }
+            | Filter::Tent { radius }
+            | Filter::Gaussian { radius, .. }
+            | Filter::Mitchell { radius, .. }
+            | Filter::Lanczos { radius } => radius,
+        }
+    }
+
+    pub fn set_radius(&mut self, new_radius: f64) {
+        match self {
+            Filter::Box { radius }
+            | Filter::Tent { radius }
+            | Filter::Gaussian { radius, .. }
+            | Filter::Mitchell { radius, .. }
+            | Filter::Lanczos { radius } => *radius = new_radius,
+        }
+    }
+
+    // Weight of a sample (dx, dy) pixels from a pixel's centre. Unnormalized;
+    // the image divides by the sum of the weights.
+    pub fn evaluate(&self, dx: f64, dy: f64) -> f64 {
+        self.evaluate_1d(dx) * self.evaluate_1d(dy)
+    }
+
+    fn evaluate_1d(&self, x: f64) -> f64 {
+        let x = x.abs();
+        match *self {
+            Filter::Box { .. } => 1.0,
+            Filter::Tent { radius } => (radius - x).max(0.0),
+            Filter::Gaussian { radius, sigma } => {
+                (gaussian(x, sigma) - gaussian(radius, sigma)).max(0.0)
+            }
+            Filter::Mitchell { radius, b, c } => mitchell((2.0 * x / radius).min(2.0), b, c),
+            Filter::Lanczos { radius } => sinc(x) * sinc(x / radius),
+        }
+    }
+
+    // Pixels along one axis of an image `size` pixels across that a sample
+    // at `position` counts towards. Offsets from the sample to their centres
+    // lie in (-radius, radius], so with the box filter's radius of a half
+    // each sample lands in exactly the pixel it was taken in.
+    pub fn pixel_range(&self, position: f64, size: u32) -> Range<u32> {
+        let radius = self.radius();
+        let first = (position - radius - 0.5).floor() + 1.0;
+        let last = (position + radius - 0.5).floor() + 1.0;
+        first.clamp(0.0, size as f64) as u32..last.clamp(0.0, size as f64) as u32
+    }
+
+    // Furthest a sample can reach past the pixel it was taken in
+    pub fn margin(&self) -> u32 {
+        (self.radius() - 0.5).ceil().max(0.0) as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn every_filter() -> [Filter; 5] {
+        [
+            Filter::box_filter(),
+            Filter::tent(),
+            Filter::gaussian(),
+            Filter::mitchell(),
+            Filter::lanczos(),
+        ]
+    }
+
+    // Sample positions across pixel `i`, including both ends
+    fn positions(i: u32) -> impl Iterator<Item = f64> {
+        (0..=1000)
+            .map(move |step| i as f64 + step as f64 / 1000.0)
+            .chain([i as f64 + 1.0 - f64::EPSILON * 16.0])
+            .filter(move |&position| position < i as f64 + 1.0)
+    }
+
+    #[test]
+    fn samples_stay_within_the_margin() {
+        let size = 64;
+        // The default radii and some in between
+        let filters = every_filter().into_iter().flat_map(|filter| {
+            [filter.radius(), 0.7, 1.0, 1.25, 2.5].map(|radius| {
+                let mut filter = filter;
+                filter.set_radius(radius);
+                filter
+            })
+        });
+        for filter in filters {
+            let margin = filter.margin();
+            for i in [0, 1, 20, size - 2, size - 1] {
+                for position in positions(i) {
+                    let range = filter.pixel_range(position, size);
+                    assert!(range.contains(&i), "{:?} at {}", filter, position);
+                    assert!(range.start + margin >= i, "{:?} at {}", filter, position);
+                    assert!(range.end <= i + margin + 1, "{:?} at {}", filter, position);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn box_filter_reaches_only_its_own_pixel() {
+        let filter = Filter::box_filter();
+        assert_eq!(filter.margin(), 0);
+        for i in [0, 1, 20, 63] {
+            for position in positions(i) {
+                assert_eq!(filter.pixel_range(position, 64), i..i + 1, "at {}", position);
+            }
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use image::codecs::hdr::{HdrDecoder, HdrEncoder};
+use image::{ImageError, Rgb, RgbImage};
+
+use crate::color::IntoColor;
+use crate::tonemap::{srgb_to_linear, ToneMapSettings};
+use crate::vec3::*;
+
+// Linear radiance per pixel, row-major with the top row first
+#[derive(Debug, Clone)]
+pub struct Framebuffer {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<Vec3>,
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Framebuffer {
+        Framebuffer {
+            width,
+            height,
+            pixels: vec![Vec3::zeros(); width as usize * height as usize],
+        }
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        y as usize * self.width as usize + x as usize
+    }
+
+    pub fn get_pixel(&self, x: u32, y: u32) -> Vec3 {
+        self.pixels[self.index(x, y)]
+    }
+
+    pub fn put_pixel(&mut self, x: u32, y: u32, color: Vec3) {
+        let index = self.index(x, y);
+        self.pixels[index] = color;
+    }
+
+    // Tone mapped, sRGB encoded 8-bit image
+    pub fn to_rgb_image(&self, tone_map: &ToneMapSettings) -> RgbImage {
+        RgbImage::from_fn(self.width, self.height, |x, y| {
+            tone_map.apply(self.get_pixel(x, y)).into_color()
+        })
+    }
+
+    // The HDR writers below store linear, scene-referred radiance as f32
+
+    pub fn save_exr(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        exr::prelude::write_rgb_file(
+            path,
+            self.width as usize,
+            self.height as usize,
+            |x, y| {
+                let color = self.get_pixel(x as u32, y as u32);
+                (color.x as f32, color.y as f32, color.z as f32)
+            },
+        )
+        .map_err(|err| match err {
+            exr::error::Error::Io(err) => err,
+            err => io::Error::other(err),
+        })
+    }
+
+    // Radiance RGBE
+    pub fn save_hdr(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let data: Vec<Rgb<f32>> = self
+            .pixels
+            .iter()
+            .map(|color| Rgb([color.x as f32, color.y as f32, color.z as f32]))
+            .collect();
+        let writer = BufWriter::new(File::create(path)?);
+        HdrEncoder::new(writer)
+            .encode(&data, self.width as usize, self.height as usize)
+            .map_err(io::Error::other)
+    }
+
+    // Portable float map; little-endian, rows stored bottom to top
+    pub fn save_pfm(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        write!(writer, "PF\n{} {}\n-1.0\n", self.width, self.height)?;
+        for y in (0..self.height).rev() {
+            for x in 0..self.width {
+                let color = self.get_pixel(x, y);
+                for channel in [color.x, color.y, color.z] {
+                    writer.write_all(&(channel as f32).to_le_bytes())?;
+                }
+            }
+        }
+        writer.flush()
+    }
+
+    // Reads an image as linear radiance, choosing the decoder by extension.
+    // Float formats are taken as is, anything else is decoded from sRGB.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Framebuffer> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("exr") => Framebuffer::load_exr(path),
+            Some("hdr") => Framebuffer::load_hdr(path),
+            _ => Framebuffer::load_srgb(path),
+        }
+    }
+
+    // Any 8 or 16-bit format the image crate reads
+    pub fn load_srgb(path: impl AsRef<Path>) -> io::Result<Framebuffer> {
+        let image = image::open(path)
+            .map_err(|err| match err {
+                ImageError::IoError(err) => err,
+                err => io::Error::other(err),
+            })?
+            .to_rgb8();
+        let decode = |c: u8| srgb_to_linear(c as f64 / 255.0);
+        Ok(Framebuffer {
+            width: image.width(),
+            height: image.height(),
+            pixels: image
+                .pixels()
+                .map(|Rgb([r, g, b])| Vec3 {
+                    x: decode(*r),
+                    y: decode(*g),
+                    z: decode(*b),
+                })
+                .collect(),
+        })
+    }
+
+    pub fn load_exr(path: impl AsRef<Path>) -> io::Result<Framebuffer> {
+        let image = exr::prelude::read_first_rgba_layer_from_file(
+            path,
+            |resolution, _| Framebuffer::new(resolution.width() as u32, resolution.height() as u32),
+            |framebuffer: &mut Framebuffer, position, (r, g, b, _a): (f32, f32, f32, f32)| {
+                let color = Vec3 {
+                    x: r as f64,
+                    y: g as f64,
+                    z: b as f64,
+                };
+                framebuffer.put_pixel(position.x() as u32, position.y() as u32, color);
+            },
+        )
+        .map_err(|err| match err {
+            exr::error::Error::Io(err) => err,
+            err => io::Error::other(err),
+        })?;
+        Ok(image.layer_data.channel_data.pixels)
+    }
+
+    // Radiance RGBE
+    pub fn load_hdr(path: impl AsRef<Path>) -> io::Result<Framebuffer> {
+        let reader = BufReader::new(File::open(path)?);
+        let decoder = HdrDecoder::new(reader).map_err(io::Error::other)?;
+        let metadata = decoder.metadata();
+        let data = decoder.read_image_hdr().map_err(io::Error::other)?;
+        Ok(Framebuffer {
+            width: metadata.width,
+            height: metadata.height,
+            pixels: data
+                .iter()
+                .map(|Rgb([r, g, b])| Vec3 {
+                    x: *r as f64,
+                    y: *g as f64,
+                    z: *b as f64,
+                })
+                .collect(),
+        })
+    }
+}
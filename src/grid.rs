@@ -0,0 +1,253 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::aabb::Aabb;
+use crate::vec3::*;
+
+// Dense 3D grid of scalar values, e.g. the density of a smoke simulation,
+// stretched over `bounds`. Values sit at voxel centres and are trilinearly
+// interpolated between them.
+pub struct VoxelGrid {
+    pub resolution: [usize; 3],
+    // x varies fastest, then y, then z
+    pub values: Vec<f32>,
+    pub bounds: Aabb,
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+// Number of voxels, which comes from the file so may not fit
+fn voxel_count(resolution: [usize; 3]) -> io::Result<usize> {
+    resolution
+        .iter()
+        .try_fold(1usize, |count, &size| count.checked_mul(size))
+        .filter(|&count| count > 0)
+        .ok_or_else(|| {
+            invalid_data(format!(
+                "invalid grid resolution {}x{}x{}",
+                resolution[0], resolution[1], resolution[2]
+            ))
+        })
+}
+
+// Values are densities, which tracking assumes are finite and never negative
+fn check_values(values: &[f32]) -> io::Result<()> {
+    match values.iter().find(|value| !(value.is_finite() && **value >= 0.0)) {
+        Some(value) => Err(invalid_data(format!("invalid voxel value {}", value))),
+        None => Ok(()),
+    }
+}
+
+// Values are placed by dividing by the extent of the bounds on each axis,
+// which must be finite and positive
+pub fn has_volume(bounds: &Aabb) -> bool {
+    let extent = bounds.extent();
+    bounds.is_finite() && extent.x > 0.0 && extent.y > 0.0 && extent.z > 0.0
+}
+
+impl VoxelGrid {
+    // `.vol` files carry their own resolution and bounds. Anything else is
+    // read as headerless little-endian f32s, which needs `resolution` and
+    // fills the unit cube.
+    pub fn load(path: impl AsRef<Path>, resolution: Option<[usize; 3]>) -> io::Result<VoxelGrid> {
+        let path = path.as_ref();
+        let bytes = fs::read(path)?;
+        let is_vol = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("vol"));
+        if is_vol {
+            VoxelGrid::from_vol(&bytes)
+        } else {
+            let resolution = resolution.ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "raw voxel files need a resolution",
+                )
+            })?;
+            VoxelGrid::from_raw(&bytes, resolution)
+        }
+    }
+
+    pub fn from_raw(bytes: &[u8], resolution: [usize; 3]) -> io::Result<VoxelGrid> {
+        let byte_len = voxel_count(resolution)?
+            .checked_mul(4)
+            .ok_or_else(|| invalid_data("grid is too large"))?;
+        if bytes.len() != byte_len {
+            return Err(invalid_data(format!(
+                "expected {} bytes for a {}x{}x{} grid, found {}",
+                byte_len,
+                resolution[0],
+                resolution[1],
+                resolution[2],
+                bytes.len()
+            )));
+        }
+        let values: Vec<f32> = bytes
+            .chunks_exact(4)
+            .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+        check_values(&values)?;
+        Ok(VoxelGrid {
+            resolution,
+            values,
+            bounds: Aabb::new(Vec3::zeros(), Vec3::ones()),
+        })
+    }
+
+    // Mitsuba's grid volume format: "VOL", version 3, then little-endian
+    // encoding (1 = f32, 3 = u8), x, y and z resolution, channel count,
+    // the bounding box as six f32s and the data. Only the first channel
+    // is kept.
+    pub fn from_vol(bytes: &[u8]) -> io::Result<VoxelGrid> {
+        const HEADER_LEN: usize = 48;
+        if bytes.len() < HEADER_LEN || &bytes[0..3] != b"VOL" {
+            return Err(invalid_data("not a .vol file"));
+        }
+        if bytes[3] != 3 {
+            return Err(invalid_data(format!("unsupported .vol version {}", bytes[3])));
+        }
+        let int_at = |offset: usize| i32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        let float_at =
+            |offset: usize| f32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap()) as f64;
+
+        let encoding = int_at(4);
+        let mut resolution = [0; 3];
+        for (axis, size) in resolution.iter_mut().enumerate() {
+            *size = usize::try_from(int_at(8 + 4 * axis))
+                .map_err(|_| invalid_data("negative .vol resolution"))?;
+        }
+        let channels = usize::try_from(int_at(20))
+            .ok()
+            .filter(|&channels| channels > 0)
+            .ok_or_else(|| invalid_data("invalid .vol channel count"))?;
+        let bounds = Aabb::new(
+            Vec3 {
+                x: float_at(24),
+                y: float_at(28),
+                z: float_at(32),
+            },
+            Vec3 {
+                x: float_at(36),
+                y: float_at(40),
+                z: float_at(44),
+            },
+        );
+
+        if !has_volume(&bounds) {
+            return Err(invalid_data("invalid .vol bounds"));
+        }
+
+        let value_size = match encoding {
+            1 => 4,
+            3 => 1,
+            _ => return Err(invalid_data(format!("unsupported .vol encoding {}", encoding))),
+        };
+        let count = voxel_count(resolution)?;
+        let data = &bytes[HEADER_LEN..];
+        let data_len = count
+            .checked_mul(channels)
+            .and_then(|len| len.checked_mul(value_size))
+            .ok_or_else(|| invalid_data(".vol grid is too large"))?;
+        if data.len() < data_len {
+            return Err(invalid_data(".vol file is truncated"));
+        }
+
+        let values: Vec<f32> = data
+            .chunks_exact(channels * value_size)
+            .take(count)
+            .map(|voxel| match encoding {
+                1 => f32::from_le_bytes(voxel[0..4].try_into().unwrap()),
+                _ => voxel[0] as f32 / 255.0,
+            })
+            .collect();
+        check_values(&values)?;
+
+        Ok(VoxelGrid {
+            resolution,
+            values,
+            bounds,
+        })
+    }
+
+    pub fn max_value(&self) -> f64 {
+        self.values.iter().fold(0.0f32, |max, &value| max.max(value)) as f64
+    }
+
+    fn voxel(&self, x: usize, y: usize, z: usize) -> f64 {
+        let [nx, ny, _] = self.resolution;
+        self.values[(z * ny + y) * nx + x] as f64
+    }
+
+    // Trilinear interpolation, zero outside the bounds
+    pub fn value(&self, p: Vec3) -> f64 {
+        let extent = self.bounds.extent();
+        let mut base = [0; 3];
+        let mut frac = [0.0; 3];
+        for axis in 0..3 {
+            let s = (p[axis] - self.bounds.minimum[axis]) / extent[axis];
+            if !(0.0..=1.0).contains(&s) {
+                return 0.0;
+            }
+            // Voxel centres are at (i + 0.5) / n; clamping holds the values
+            // constant over the outer half voxel
+            let n = self.resolution[axis];
+            let x = (s * n as f64 - 0.5).clamp(0.0, (n - 1) as f64);
+            let i = (x as usize).min(n.saturating_sub(2));
+            base[axis] = i;
+            frac[axis] = x - i as f64;
+        }
+
+        let mut result = 0.0;
+        for corner in 0..8 {
+            let mut weight = 1.0;
+            let mut index = [0; 3];
+            for axis in 0..3 {
+                let upper = corner >> axis & 1 == 1;
+                weight *= if upper { frac[axis] } else { 1.0 - frac[axis] };
+                index[axis] = (base[axis] + upper as usize).min(self.resolution[axis] - 1);
+            }
+            if weight > 0.0 {
+                result += weight * self.voxel(index[0], index[1], index[2]);
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A 2x1x1 f32 .vol file
+    fn vol_file(bounds: [f32; 6]) -> Vec<u8> {
+        let mut bytes = b"VOL\x03".to_vec();
+        for int in [1, 2, 1, 1, 1] {
+            bytes.extend_from_slice(&i32::to_le_bytes(int));
+        }
+        for float in bounds.into_iter().chain([0.5, 1.0]) {
+            bytes.extend_from_slice(&f32::to_le_bytes(float));
+        }
+        bytes
+    }
+
+    #[test]
+    fn vol_bounds_must_have_volume() {
+        let grid = VoxelGrid::from_vol(&vol_file([0.0, 0.0, 0.0, 2.0, 1.0, 1.0])).unwrap();
+        assert_eq!(grid.values, [0.5, 1.0]);
+        assert!((grid.value(Vec3 { x: 1.5, y: 0.5, z: 0.5 }) - 1.0).abs() < 1e-9);
+
+        for bounds in [
+            [0.0, 0.0, 0.0, 2.0, 0.0, 1.0],
+            [0.0, 0.0, 0.0, -2.0, 1.0, 1.0],
+            [0.0, 0.0, 0.0, f32::INFINITY, 1.0, 1.0],
+            [f32::NAN, 0.0, 0.0, 2.0, 1.0, 1.0],
+        ] {
+            let err = VoxelGrid::from_vol(&vol_file(bounds)).err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{:?}", bounds);
+        }
+    }
+}
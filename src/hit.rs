@@ -1,9 +1,16 @@
-use crate::material;
 use crate::vec3::*;
 use crate::ray::*;
 use crate::material::MaterialEnum;
+use crate::aabb::Aabb;
+use crate::sampler::SamplerEnum;
 
 use crate::sphere::Sphere;
+use crate::moving_sphere::MovingSphere;
+use crate::bvh::BvhNode;
+use crate::triangle::Triangle;
+use crate::mesh::MeshTriangle;
+use crate::instance::Instance;
+use crate::medium::{ConstantMedium, GridMedium, Medium};
 
 use enum_dispatch::enum_dispatch;
 
@@ -13,20 +20,54 @@ pub struct HitRecord<'a> {
     pub normal: Vec3,
     pub mat_ref: &'a MaterialEnum,
     pub t: f64,
-    pub front_face: bool
+    pub front_face: bool,
+    // Weights of the three vertices when a triangle was hit
+    pub barycentric: Option<[f64; 3]>,
+    // Surface coordinates for texture lookups
+    pub u: f64,
+    pub v: f64,
 }
 
 impl<'a> HitRecord<'a> {
     pub fn new(p: Vec3, t: f64, mat_ref: &'a MaterialEnum, outward_normal: Vec3, r: &Ray) -> HitRecord<'a> {
         let front_face = r.direction.dot(outward_normal) < 0.0;
         let normal = if front_face {outward_normal} else {-outward_normal};
-        HitRecord { p, normal, mat_ref: &mat_ref, t, front_face }
+        HitRecord { p, normal, mat_ref, t, front_face, barycentric: None, u: 0.0, v: 0.0 }
     }
 }
 
 #[enum_dispatch(Hittable)]
 pub trait Hit {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>>;
+
+    // None if the object has no finite bounds
+    fn bounding_box(&self) -> Option<Aabb>;
+
+    // Emitters that can be sampled directly by the integrator.
+    // Objects returning true must implement pdf_value and random_direction.
+    fn is_light(&self) -> bool {
+        false
+    }
+
+    // Solid angle density, as seen from `origin`, of random_direction
+    // generating `direction`
+    fn pdf_value(&self, _origin: Vec3, _direction: Vec3) -> f64 {
+        0.0
+    }
+
+    // Direction from `origin` towards a random point on the object
+    fn random_direction(&self, _origin: Vec3, _sampler: &mut SamplerEnum) -> Vec3 {
+        Vec3 {
+            x: 1.0,
+            y: 0.0,
+            z: 0.0,
+        }
+    }
+
+    // Participating media, which the integrator samples along every ray
+    fn as_medium(&self) -> Option<&dyn Medium> {
+        None
+    }
 }
 
 
@@ -36,25 +77,40 @@ pub trait Hit {
 #[enum_dispatch]
 pub enum Hittable {
     Sphere,
+    MovingSphere,
+    BvhNode,
+    Triangle,
+    MeshTriangle,
+    Instance,
+    ConstantMedium,
+    GridMedium,
 }
 
 pub type HittableList = Vec<Hittable>;
 
 impl Hit for HittableList {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         let mut closest_t_so_far = t_max;
-        let mut closest_hit: Option<HitRecord> = None;
+        let mut closest_hit: Option<HitRecord<'_>> = None;
 
         for hittable in self {
-            let option_rec = hittable.hit(r, t_min, closest_t_so_far);
-            match option_rec {
-                Some(rec) => {
-                    closest_t_so_far = rec.t;
-                    closest_hit = Some(rec);
-                },
-                None => ()
+            if let Some(rec) = hittable.hit(r, t_min, closest_t_so_far) {
+                closest_t_so_far = rec.t;
+                closest_hit = Some(rec);
             }
         }
         closest_hit
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let mut output_box = Aabb::empty();
+        for hittable in self {
+            output_box = output_box.surrounding(hittable.bounding_box()?);
+        }
+        Some(output_box)
+    }
 }
\ No newline at end of file
@@ -0,0 +1,52 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hit::*;
+use crate::ray::Ray;
+use crate::transform::Transform;
+use crate::vec3::*;
+
+// A shared hittable placed in the world by an affine transform. Many
+// instances can point at the same object without copying its geometry.
+//
+// Instances are not sampled as lights, since their solid angle densities
+// change under scaling; emissive instances are still found by scattered rays.
+pub struct Instance {
+    pub object: Arc<Hittable>,
+    // Boxed, as its three matrices would make every Hittable this large
+    pub transform: Box<Transform>,
+    bbox: Option<Aabb>,
+}
+
+impl Instance {
+    pub fn new(object: Arc<Hittable>, transform: Transform) -> Instance {
+        let bbox = object
+            .bounding_box()
+            .map(|bbox| transform.bounding_box(&bbox));
+        Instance {
+            object,
+            transform: Box::new(transform),
+            bbox,
+        }
+    }
+}
+
+impl Hit for Instance {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let object_ray = self.transform.inverse_ray(r);
+        let mut rec = self.object.hit(&object_ray, t_min, t_max)?;
+
+        // t carries over unchanged because the object-space direction isn't
+        // normalized. The normal still faces against the ray afterwards, as
+        // the inverse transpose preserves the sign of dot products with
+        // transformed directions.
+        rec.p = self.transform.point(rec.p);
+        rec.normal = self.transform.normal(rec.normal).unit_vec();
+
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bbox
+    }
+}
@@ -0,0 +1,53 @@
+//! Ray Tracing in One Weekend as a library.
+//!
+//! Build a world from `Hittable`s (or load a `SceneDescription`), then hand it
+//! to a `Renderer` to get a linear `Framebuffer` back.
+
+pub mod aabb;
+pub mod background;
+pub mod bvh;
+pub mod camera;
+pub mod checkpoint;
+pub mod color;
+pub mod distribution;
+pub mod environment;
+pub mod filter;
+pub mod framebuffer;
+pub mod grid;
+pub mod hit;
+pub mod instance;
+pub mod lights;
+pub mod material;
+pub mod medium;
+pub mod mesh;
+pub mod moving_sphere;
+pub mod obj;
+pub mod onb;
+pub mod perlin;
+pub mod presets;
+pub mod ray;
+pub mod render;
+pub mod sampler;
+pub mod scene;
+pub mod settings;
+pub mod sphere;
+pub mod texture;
+pub mod tiles;
+pub mod tonemap;
+pub mod transform;
+pub mod triangle;
+pub mod uniform_wrapper;
+pub mod vec3;
+
+pub use background::Background;
+pub use bvh::BvhNode;
+pub use camera::{Camera, CameraEnum};
+pub use environment::EnvironmentMap;
+pub use framebuffer::Framebuffer;
+pub use hit::{Hit, Hittable, HittableList};
+pub use material::MaterialEnum;
+pub use render::Renderer;
+pub use scene::{Scene, SceneDescription, SceneError};
+pub use settings::RenderSettings;
+pub use tonemap::{ToneMapOperator, ToneMapSettings};
+pub use vec3::Vec3;
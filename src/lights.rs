@@ -0,0 +1,85 @@
+use crate::background::Background;
+use crate::environment::EnvironmentMap;
+use crate::hit::*;
+use crate::sampler::{Sampler, SamplerEnum};
+use crate::vec3::Vec3;
+
+// Emitters the integrator samples directly, chosen with equal probability.
+// An environment map counts as one more light.
+pub struct LightList<'a> {
+    lights: Vec<&'a Hittable>,
+    environment: Option<&'a EnvironmentMap>,
+}
+
+impl<'a> LightList<'a> {
+    // Collects every object in `world` (looking inside BVH nodes) whose
+    // is_light returns true
+    pub fn from_world(world: &'a Hittable) -> LightList<'a> {
+        let mut lights = Vec::new();
+        collect_lights(world, &mut lights);
+        LightList {
+            lights,
+            environment: None,
+        }
+    }
+
+    pub fn from_list(world: &'a HittableList) -> LightList<'a> {
+        let mut lights = Vec::new();
+        for hittable in world {
+            collect_lights(hittable, &mut lights);
+        }
+        LightList {
+            lights,
+            environment: None,
+        }
+    }
+
+    // Adds the background as a light if it is an environment map
+    pub fn with_background(mut self, background: &'a Background) -> LightList<'a> {
+        self.environment = background.environment();
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.lights.len() + usize::from(self.environment.is_some())
+    }
+
+    // Density of random_direction over solid angle, averaged over the lights
+    pub fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let sum: f64 = self
+            .lights
+            .iter()
+            .map(|light| light.pdf_value(origin, direction))
+            .sum::<f64>()
+            + self
+                .environment
+                .map_or(0.0, |environment| environment.pdf_value(direction));
+        sum / self.len() as f64
+    }
+
+    pub fn random_direction(&self, origin: Vec3, sampler: &mut SamplerEnum) -> Vec3 {
+        let index = ((sampler.get_1d() * self.len() as f64) as usize).min(self.len() - 1);
+        match self.lights.get(index) {
+            Some(light) => light.random_direction(origin, sampler),
+            None => self
+                .environment
+                .expect("index past the lights means the environment is present")
+                .random_direction(sampler),
+        }
+    }
+}
+
+fn collect_lights<'a>(hittable: &'a Hittable, lights: &mut Vec<&'a Hittable>) {
+    match hittable {
+        Hittable::BvhNode(node) => node.for_each_object(&mut |child| collect_lights(child, lights)),
+        _ if hittable.is_light() => lights.push(hittable),
+        _ => (),
+    }
+}
@@ -1,17 +1,21 @@
 use crate::hit::HitRecord;
+use crate::onb::Onb;
 use crate::ray::Ray;
-use crate::uniform_wrapper::*;
+use crate::texture::{Texture, TextureEnum};
+use crate::sampler::{Sampler, SamplerEnum};
 use crate::vec3::{Reflect, Refract, Vec3, VecLength, VecProducts};
 
 use enum_dispatch::enum_dispatch;
-use rand::{
-    distributions::Uniform,
-    prelude::{Distribution, ThreadRng, Rng},
-};
+use std::f64::consts::PI;
 
+// `attenuation` is the BSDF times the cosine term divided by `pdf`, the
+// solid angle density the direction was sampled with. Specular (delta)
+// lobes have no density; `pdf` is 0 and `specular` is set.
 pub struct ScatterResult {
     pub attenuation: Vec3,
     pub ray: Ray,
+    pub pdf: f64,
+    pub specular: bool,
 }
 
 #[enum_dispatch(MaterialEnum)]
@@ -20,31 +24,55 @@ pub trait Material {
         &self,
         ray_in: &Ray,
         hit_rec: &HitRecord,
-        unigen0_1: &mut UniGen0_1,
-        unigen_neg1_1: &mut UniGenNeg1_1
+        sampler: &mut SamplerEnum,
     ) -> Option<ScatterResult>;
+
+    // Radiance given off by the surface itself
+    fn emitted(&self, _hit_rec: &HitRecord) -> Vec3 {
+        Vec3::zeros()
+    }
+
+    fn is_emissive(&self) -> bool {
+        false
+    }
+
+    // BSDF times the cosine term, for light arriving along `direction`
+    // and leaving back along ray_in. Zero for specular lobes.
+    fn eval(&self, _ray_in: &Ray, _hit_rec: &HitRecord, _direction: Vec3) -> Vec3 {
+        Vec3::zeros()
+    }
+
+    // Density over solid angle with which scatter picks `direction`
+    fn scattering_pdf(&self, _ray_in: &Ray, _hit_rec: &HitRecord, _direction: Vec3) -> f64 {
+        0.0
+    }
 }
 
 #[enum_dispatch]
+#[derive(Clone)]
 pub enum MaterialEnum {
     Lambertian,
     Metal,
     Dielectric,
+    DiffuseLight,
+    Isotropic,
+    HenyeyGreenstein,
 }
 
+#[derive(Clone)]
 pub struct Lambertian {
-    pub albedo: Vec3,
+    pub albedo: TextureEnum,
 }
 
 impl Material for Lambertian {
     fn scatter(
         &self,
-        _ray_in: &Ray,
+        ray_in: &Ray,
         hit_rec: &HitRecord,
-        unigen0_1: &mut UniGen0_1,
-        unigen_neg1_1: &mut UniGenNeg1_1
+        sampler: &mut SamplerEnum,
     ) -> Option<ScatterResult> {
-        let scatter_direction_maybe = hit_rec.normal + Vec3::random_in_unit_sphere(unigen_neg1_1);
+        // Cosine-weighted, matching eval
+        let scatter_direction_maybe = hit_rec.normal + Vec3::unit_vector_from(sampler.get_2d());
 
         let scatter_direction = if scatter_direction_maybe.near_zero() {
             hit_rec.normal
@@ -55,17 +83,35 @@ impl Material for Lambertian {
         let scattered_ray = Ray {
             origin: hit_rec.p,
             direction: scatter_direction,
+            time: ray_in.time,
         };
 
         Some(ScatterResult {
-            attenuation: self.albedo,
+            attenuation: self.albedo.value(hit_rec.u, hit_rec.v, hit_rec.p),
+            pdf: self.scattering_pdf(ray_in, hit_rec, scatter_direction),
             ray: scattered_ray,
+            specular: false,
         })
     }
+
+    fn eval(&self, ray_in: &Ray, hit_rec: &HitRecord, direction: Vec3) -> Vec3 {
+        self.albedo.value(hit_rec.u, hit_rec.v, hit_rec.p)
+            * self.scattering_pdf(ray_in, hit_rec, direction)
+    }
+
+    fn scattering_pdf(&self, _ray_in: &Ray, hit_rec: &HitRecord, direction: Vec3) -> f64 {
+        let cosine = hit_rec.normal.dot(direction.unit_vec());
+        if cosine <= 0.0 {
+            0.0
+        } else {
+            cosine / PI
+        }
+    }
 }
 
+#[derive(Clone)]
 pub struct Metal {
-    pub albedo: Vec3,
+    pub albedo: TextureEnum,
     pub fuzz: f64, // must be between 0-1
 }
 
@@ -74,25 +120,59 @@ impl Material for Metal {
         &self,
         ray_in: &Ray,
         hit_rec: &HitRecord,
-        unigen0_1: &mut UniGen0_1,
-        unigen_neg1_1: &mut UniGenNeg1_1
+        sampler: &mut SamplerEnum,
     ) -> Option<ScatterResult> {
         let reflected = ray_in.direction.unit_vec().reflect(hit_rec.normal);
         let scattered = Ray {
             origin: hit_rec.p,
-            direction: reflected + self.fuzz * Vec3::random_in_unit_sphere(unigen_neg1_1),
+            direction: reflected
+                + self.fuzz * Vec3::in_unit_sphere_from(sampler.get_2d(), sampler.get_1d()),
+            time: ray_in.time,
         };
         if scattered.direction.dot(hit_rec.normal) > 0.0 {
             Some(ScatterResult {
-                attenuation: self.albedo,
+                attenuation: self.albedo.value(hit_rec.u, hit_rec.v, hit_rec.p),
+                pdf: self.scattering_pdf(ray_in, hit_rec, scattered.direction),
                 ray: scattered,
+                specular: self.fuzz <= 0.0,
             })
         } else {
             None
         }
     }
+
+    // Directions absorbed below the surface are simply lost, so the BSDF
+    // times cosine is the albedo times the sampling density
+    fn eval(&self, ray_in: &Ray, hit_rec: &HitRecord, direction: Vec3) -> Vec3 {
+        self.albedo.value(hit_rec.u, hit_rec.v, hit_rec.p)
+            * self.scattering_pdf(ray_in, hit_rec, direction)
+    }
+
+    // Density of the direction to a uniform point in the fuzz sphere around
+    // the mirror direction: the length of the ray's chord through the sphere,
+    // integrated as t^2 dt, over the sphere's volume
+    fn scattering_pdf(&self, ray_in: &Ray, hit_rec: &HitRecord, direction: Vec3) -> f64 {
+        let direction = direction.unit_vec();
+        if self.fuzz <= 0.0 || direction.dot(hit_rec.normal) <= 0.0 {
+            return 0.0;
+        }
+
+        let reflected = ray_in.direction.unit_vec().reflect(hit_rec.normal);
+        let c = direction.dot(reflected);
+        let discriminant = c * c - 1.0 + self.fuzz * self.fuzz;
+        if discriminant <= 0.0 {
+            return 0.0;
+        }
+        let t_near = (c - discriminant.sqrt()).max(0.0);
+        let t_far = c + discriminant.sqrt();
+        if t_far <= 0.0 {
+            return 0.0;
+        }
+        (t_far.powi(3) - t_near.powi(3)) / (4.0 * PI * self.fuzz.powi(3))
+    }
 }
 
+#[derive(Clone)]
 pub struct Dielectric {
     pub ir: f64,
 }
@@ -112,8 +192,7 @@ impl Material for Dielectric {
         &self,
         ray_in: &Ray,
         hit_rec: &HitRecord,
-        unigen0_1: &mut UniGen0_1,
-        unigen_neg1_1: &mut UniGenNeg1_1
+        sampler: &mut SamplerEnum,
     ) -> Option<ScatterResult> {
         let refraction_ratio = if hit_rec.front_face {
             1.0 / self.ir
@@ -128,8 +207,9 @@ impl Material for Dielectric {
 
         let reflectance = Dielectric::reflectance(cos_theta, refraction_ratio);
 
-        let cannot_refract = (refraction_ratio * sin_theta > 1.0)
-            || (reflectance > unigen0_1.sample());
+        // Drawn either way so later dimensions don't depend on the branch
+        let u = sampler.get_1d();
+        let cannot_refract = (refraction_ratio * sin_theta > 1.0) || (reflectance > u);
 
         let direction = if cannot_refract {
             unit_direction.reflect(hit_rec.normal)
@@ -146,7 +226,137 @@ impl Material for Dielectric {
             ray: Ray {
                 origin: hit_rec.p,
                 direction,
+                time: ray_in.time,
+            },
+            pdf: 0.0,
+            specular: true,
+        })
+    }
+}
+
+// Emits light equally in all directions and from both sides, absorbs everything
+#[derive(Clone)]
+pub struct DiffuseLight {
+    pub emit: Vec3,
+}
+
+impl Material for DiffuseLight {
+    fn scatter(
+        &self,
+        _ray_in: &Ray,
+        _hit_rec: &HitRecord,
+        _sampler: &mut SamplerEnum,
+    ) -> Option<ScatterResult> {
+        None
+    }
+
+    fn emitted(&self, _hit_rec: &HitRecord) -> Vec3 {
+        self.emit
+    }
+
+    fn is_emissive(&self) -> bool {
+        true
+    }
+}
+
+// Phase function of a participating medium that scatters equally in all
+// directions. Used for points inside volumes rather than on surfaces.
+#[derive(Clone)]
+pub struct Isotropic {
+    pub albedo: TextureEnum,
+}
+
+impl Material for Isotropic {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        hit_rec: &HitRecord,
+        sampler: &mut SamplerEnum,
+    ) -> Option<ScatterResult> {
+        Some(ScatterResult {
+            attenuation: self.albedo.value(hit_rec.u, hit_rec.v, hit_rec.p),
+            ray: Ray {
+                origin: hit_rec.p,
+                direction: Vec3::unit_vector_from(sampler.get_2d()),
+                time: ray_in.time,
+            },
+            pdf: 1.0 / (4.0 * PI),
+            specular: false,
+        })
+    }
+
+    // No cosine term inside a volume
+    fn eval(&self, _ray_in: &Ray, hit_rec: &HitRecord, _direction: Vec3) -> Vec3 {
+        self.albedo.value(hit_rec.u, hit_rec.v, hit_rec.p) / (4.0 * PI)
+    }
+
+    fn scattering_pdf(&self, _ray_in: &Ray, _hit_rec: &HitRecord, _direction: Vec3) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+}
+
+// Phase function of a medium that scatters mostly forwards (g > 0) or
+// backwards (g < 0), like smoke and clouds. g = 0 is isotropic.
+#[derive(Clone)]
+pub struct HenyeyGreenstein {
+    pub albedo: TextureEnum,
+    pub g: f64, // must be between -1 and 1
+}
+
+impl HenyeyGreenstein {
+    // Density for directions at angle theta to the direction of travel
+    fn phase(&self, cos_theta: f64) -> f64 {
+        let g = self.g;
+        let denominator = 1.0 + g * g - 2.0 * g * cos_theta;
+        (1.0 - g * g) / (4.0 * PI * denominator * denominator.max(0.0).sqrt())
+    }
+}
+
+impl Material for HenyeyGreenstein {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        hit_rec: &HitRecord,
+        sampler: &mut SamplerEnum,
+    ) -> Option<ScatterResult> {
+        // Inverting the CDF of cos(theta)
+        let g = self.g;
+        let (u, u_phi) = sampler.get_2d();
+        let cos_theta = if g.abs() < 1e-3 {
+            1.0 - 2.0 * u
+        } else {
+            let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * u);
+            ((1.0 + g * g - s * s) / (2.0 * g)).clamp(-1.0, 1.0)
+        };
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * u_phi;
+
+        let onb = Onb::from_w(ray_in.direction);
+        let direction = onb.local(Vec3 {
+            x: sin_theta * phi.cos(),
+            y: sin_theta * phi.sin(),
+            z: cos_theta,
+        });
+
+        // Sampled exactly, so the phase function and pdf cancel
+        Some(ScatterResult {
+            attenuation: self.albedo.value(hit_rec.u, hit_rec.v, hit_rec.p),
+            ray: Ray {
+                origin: hit_rec.p,
+                direction,
+                time: ray_in.time,
             },
+            pdf: self.phase(cos_theta),
+            specular: false,
         })
     }
+
+    fn eval(&self, ray_in: &Ray, hit_rec: &HitRecord, direction: Vec3) -> Vec3 {
+        self.albedo.value(hit_rec.u, hit_rec.v, hit_rec.p)
+            * self.scattering_pdf(ray_in, hit_rec, direction)
+    }
+
+    fn scattering_pdf(&self, ray_in: &Ray, _hit_rec: &HitRecord, direction: Vec3) -> f64 {
+        self.phase(ray_in.direction.unit_vec().dot(direction.unit_vec()))
+    }
 }
@@ -0,0 +1,268 @@
+use crate::aabb::Aabb;
+use crate::grid::VoxelGrid;
+use crate::hit::*;
+use crate::material::{Isotropic, MaterialEnum};
+use crate::ray::*;
+use crate::texture::TextureEnum;
+use crate::sampler::{Sampler, SamplerEnum};
+use crate::vec3::*;
+
+// Volume that scatters light at random points inside it rather than at a
+// surface. Distances are sampled by the integrator, which asks every medium
+// for its first scattering event along each path segment.
+pub trait Medium: Sync {
+    // Ray parameter in (t_min, t_max) where `r` first scatters, or None if
+    // it passes through that stretch
+    fn sample_distance(
+        &self,
+        r: &Ray,
+        t_min: f64,
+        t_max: f64,
+        sampler: &mut SamplerEnum,
+    ) -> Option<f64>;
+
+    // Fraction of light that gets from t_min to t_max without scattering.
+    // May be a random estimate whose expected value is the transmittance.
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64, sampler: &mut SamplerEnum) -> f64;
+
+    // Material giving the phase function at scattering events
+    fn phase_function(&self) -> &MaterialEnum;
+}
+
+// Every medium in the world, gathered once before rendering
+pub struct MediumList<'a> {
+    media: Vec<&'a dyn Medium>,
+}
+
+impl<'a> MediumList<'a> {
+    // Looks inside BVH nodes but not instances, which is why prototypes
+    // can't contain media
+    pub fn from_world(world: &'a Hittable) -> MediumList<'a> {
+        let mut media = Vec::new();
+        collect_media(world, &mut media);
+        MediumList { media }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.media.is_empty()
+    }
+
+    // Nearest scattering event before t_max in any medium. Taking the closest
+    // of independent samples is the same as sampling the combined density.
+    pub fn sample_interaction(
+        &self,
+        r: &Ray,
+        t_min: f64,
+        t_max: f64,
+        sampler: &mut SamplerEnum,
+    ) -> Option<HitRecord<'a>> {
+        let mut closest: Option<(f64, &'a dyn Medium)> = None;
+        for &medium in &self.media {
+            let t_limit = closest.map_or(t_max, |(t, _)| t);
+            if let Some(t) = medium.sample_distance(r, t_min, t_limit, sampler) {
+                closest = Some((t, medium));
+            }
+        }
+
+        closest.map(|(t, medium)| {
+            // There's no surface, so the "normal" just faces back along the ray
+            HitRecord::new(r.at(t), t, medium.phase_function(), -r.direction.unit_vec(), r)
+        })
+    }
+
+    pub fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64, sampler: &mut SamplerEnum) -> f64 {
+        self.media
+            .iter()
+            .map(|medium| medium.transmittance(r, t_min, t_max, sampler))
+            .product()
+    }
+}
+
+fn collect_media<'a>(hittable: &'a Hittable, media: &mut Vec<&'a dyn Medium>) {
+    match hittable {
+        Hittable::BvhNode(node) => node.for_each_object(&mut |child| collect_media(child, media)),
+        _ => media.extend(hittable.as_medium()),
+    }
+}
+
+// Uniform density inside a closed, convex boundary, e.g. fog or smoke
+// filling a sphere. The boundary's own material is ignored.
+pub struct ConstantMedium {
+    pub boundary: Box<Hittable>,
+    pub density: f64,
+    pub phase_function: MaterialEnum,
+}
+
+impl ConstantMedium {
+    pub fn new(boundary: Hittable, density: f64, albedo: TextureEnum) -> ConstantMedium {
+        ConstantMedium {
+            boundary: Box::new(boundary),
+            density,
+            phase_function: Isotropic { albedo }.into(),
+        }
+    }
+
+    // Part of (t_min, t_max) that lies inside the boundary
+    fn inside(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<(f64, f64)> {
+        let enter = self.boundary.hit(r, f64::NEG_INFINITY, f64::INFINITY)?;
+        let exit = self.boundary.hit(r, enter.t + 0.0001, f64::INFINITY)?;
+
+        let t0 = enter.t.max(t_min);
+        let t1 = exit.t.min(t_max);
+        if t0 < t1 {
+            Some((t0, t1))
+        } else {
+            None
+        }
+    }
+}
+
+impl Medium for ConstantMedium {
+    fn sample_distance(
+        &self,
+        r: &Ray,
+        t_min: f64,
+        t_max: f64,
+        sampler: &mut SamplerEnum,
+    ) -> Option<f64> {
+        let (t0, t1) = self.inside(r, t_min, t_max)?;
+        let ray_length = r.direction.length();
+        let distance_inside = (t1 - t0) * ray_length;
+        // Exponential free flight; 1 - u keeps the logarithm finite
+        let hit_distance = -(1.0 - sampler.get_1d()).ln() / self.density;
+        if hit_distance < distance_inside {
+            Some(t0 + hit_distance / ray_length)
+        } else {
+            None
+        }
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64, _sampler: &mut SamplerEnum) -> f64 {
+        match self.inside(r, t_min, t_max) {
+            Some((t0, t1)) => (-self.density * (t1 - t0) * r.direction.length()).exp(),
+            None => 1.0,
+        }
+    }
+
+    fn phase_function(&self) -> &MaterialEnum {
+        &self.phase_function
+    }
+}
+
+// Surfaces never report a medium; it only shows up through the integrator
+impl Hit for ConstantMedium {
+    fn hit(&self, _r: &Ray, _t_min: f64, _t_max: f64) -> Option<HitRecord<'_>> {
+        None
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.boundary.bounding_box()
+    }
+
+    fn as_medium(&self) -> Option<&dyn Medium> {
+        Some(self)
+    }
+}
+
+// Density varying through a voxel grid, e.g. smoke or clouds from a
+// simulation cache. Distances are sampled by delta tracking against the
+// majorant, the largest density anywhere in the grid: free flights are
+// drawn as if the whole box had that density, and each tentative collision
+// is kept with probability density / majorant. The rest are null collisions
+// that let the ray carry on unchanged.
+pub struct GridMedium {
+    pub grid: VoxelGrid,
+    // Multiplies every value in the grid
+    pub density_scale: f64,
+    pub majorant: f64,
+    pub phase_function: MaterialEnum,
+}
+
+impl GridMedium {
+    pub fn new(grid: VoxelGrid, density_scale: f64, phase_function: MaterialEnum) -> GridMedium {
+        let majorant = grid.max_value() * density_scale;
+        GridMedium {
+            grid,
+            density_scale,
+            majorant,
+            phase_function,
+        }
+    }
+
+    pub fn density(&self, p: Vec3) -> f64 {
+        self.grid.value(p) * self.density_scale
+    }
+
+    // Exponential step to the next tentative collision, in ray parameter units
+    fn free_flight(&self, ray_length: f64, sampler: &mut SamplerEnum) -> f64 {
+        -(1.0 - sampler.get_1d()).ln() / (self.majorant * ray_length)
+    }
+}
+
+impl Medium for GridMedium {
+    fn sample_distance(
+        &self,
+        r: &Ray,
+        t_min: f64,
+        t_max: f64,
+        sampler: &mut SamplerEnum,
+    ) -> Option<f64> {
+        if self.majorant <= 0.0 {
+            return None;
+        }
+        let (mut t, t1) = self.grid.bounds.clip(r, t_min, t_max)?;
+        let ray_length = r.direction.length();
+        loop {
+            t += self.free_flight(ray_length, sampler);
+            if t >= t1 {
+                return None;
+            }
+            if sampler.get_1d() * self.majorant < self.density(r.at(t)) {
+                return Some(t);
+            }
+        }
+    }
+
+    // Ratio tracking: the same tentative collisions as delta tracking, but
+    // instead of stopping at a real one, each collision scales the estimate
+    // by the chance it was a null collision
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64, sampler: &mut SamplerEnum) -> f64 {
+        if self.majorant <= 0.0 {
+            return 1.0;
+        }
+        let (mut t, t1) = match self.grid.bounds.clip(r, t_min, t_max) {
+            Some(interval) => interval,
+            None => return 1.0,
+        };
+        let ray_length = r.direction.length();
+        let mut transmittance = 1.0;
+        loop {
+            t += self.free_flight(ray_length, sampler);
+            if t >= t1 {
+                return transmittance;
+            }
+            transmittance *= 1.0 - self.density(r.at(t)) / self.majorant;
+            if transmittance <= 0.0 {
+                return 0.0;
+            }
+        }
+    }
+
+    fn phase_function(&self) -> &MaterialEnum {
+        &self.phase_function
+    }
+}
+
+impl Hit for GridMedium {
+    fn hit(&self, _r: &Ray, _t_min: f64, _t_max: f64) -> Option<HitRecord<'_>> {
+        None
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.grid.bounds)
+    }
+
+    fn as_medium(&self) -> Option<&dyn Medium> {
+        Some(self)
+    }
+}
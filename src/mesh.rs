@@ -0,0 +1,129 @@
+use std::ops::Range;
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hit::*;
+use crate::material::{Material, MaterialEnum};
+use crate::ray::*;
+use crate::triangle::*;
+use crate::sampler::SamplerEnum;
+use crate::vec3::*;
+
+// Indices into the owning mesh's buffers.
+// Normals and UVs are indexed separately from positions, as in OBJ files.
+#[derive(Debug, Clone, Copy)]
+pub struct MeshFace {
+    pub vertices: [usize; 3],
+    pub normals: Option<[usize; 3]>,
+    pub uvs: Option<[usize; 3]>,
+    pub material: usize,
+}
+
+// Vertex data shared by every triangle of the mesh
+pub struct TriangleMesh {
+    pub positions: Vec<Vec3>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<[f64; 2]>,
+    pub faces: Vec<MeshFace>,
+    pub materials: Vec<MaterialEnum>,
+}
+
+impl TriangleMesh {
+    // One hittable per face, each holding a reference to the shared buffers.
+    // Push these into a HittableList and build a BvhNode over it.
+    pub fn triangles(mesh: &Arc<TriangleMesh>) -> HittableList {
+        TriangleMesh::triangles_in(mesh, 0..mesh.faces.len())
+    }
+
+    pub fn triangles_in(mesh: &Arc<TriangleMesh>, faces: Range<usize>) -> HittableList {
+        faces
+            .map(|face| {
+                MeshTriangle {
+                    mesh: Arc::clone(mesh),
+                    face,
+                }
+                .into()
+            })
+            .collect()
+    }
+
+    fn face_positions(&self, face: &MeshFace) -> [Vec3; 3] {
+        [
+            self.positions[face.vertices[0]],
+            self.positions[face.vertices[1]],
+            self.positions[face.vertices[2]],
+        ]
+    }
+}
+
+pub struct MeshTriangle {
+    mesh: Arc<TriangleMesh>,
+    face: usize,
+}
+
+impl Hit for MeshTriangle {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let face = &self.mesh.faces[self.face];
+        let vertices = self.mesh.face_positions(face);
+        let (t, barycentric) = intersect_triangle(r, &vertices, t_min, t_max)?;
+
+        let geometric_normal = (vertices[1] - vertices[0])
+            .cross(vertices[2] - vertices[0])
+            .unit_vec();
+
+        // Smooth shading: interpolate the vertex normals across the face
+        let shading_normal = face.normals.map(|indices| {
+            (barycentric[0] * self.mesh.normals[indices[0]]
+                + barycentric[1] * self.mesh.normals[indices[1]]
+                + barycentric[2] * self.mesh.normals[indices[2]])
+                .unit_vec()
+        });
+
+        // Vertex normals decide which side is outside, so inconsistent
+        // winding in the source data doesn't flip front_face
+        let outward_normal = match shading_normal {
+            Some(n) if n.dot(geometric_normal) < 0.0 => -geometric_normal,
+            _ => geometric_normal,
+        };
+
+        let material = &self.mesh.materials[face.material];
+        let mut hit_record = HitRecord::new(r.at(t), t, material, outward_normal, r);
+        if let Some(n) = shading_normal {
+            hit_record.normal = if n.dot(hit_record.normal) < 0.0 { -n } else { n };
+        }
+        hit_record.barycentric = Some(barycentric);
+        [hit_record.u, hit_record.v] = match face.uvs {
+            Some(indices) => {
+                let uv = |k: usize| self.mesh.uvs[indices[k]];
+                [0, 1].map(|axis| {
+                    barycentric[0] * uv(0)[axis]
+                        + barycentric[1] * uv(1)[axis]
+                        + barycentric[2] * uv(2)[axis]
+                })
+            }
+            None => [barycentric[1], barycentric[2]],
+        };
+
+        Some(hit_record)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let face = &self.mesh.faces[self.face];
+        Some(triangle_bounding_box(&self.mesh.face_positions(face)))
+    }
+
+    fn is_light(&self) -> bool {
+        let face = &self.mesh.faces[self.face];
+        self.mesh.materials[face.material].is_emissive()
+    }
+
+    fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f64 {
+        let face = &self.mesh.faces[self.face];
+        triangle_pdf_value(&self.mesh.face_positions(face), origin, direction)
+    }
+
+    fn random_direction(&self, origin: Vec3, sampler: &mut SamplerEnum) -> Vec3 {
+        let face = &self.mesh.faces[self.face];
+        triangle_random_direction(&self.mesh.face_positions(face), origin, sampler)
+    }
+}
@@ -0,0 +1,41 @@
+use crate::aabb::Aabb;
+use crate::hit::*;
+use crate::material::MaterialEnum;
+use crate::ray::*;
+use crate::sphere::{hit_sphere, sphere_bounding_box};
+use crate::vec3::*;
+
+// Sphere moving in a straight line from center0 at time0 to center1 at time1.
+// It rests at the end points outside that interval, so the bounding box
+// covers every ray time.
+pub struct MovingSphere {
+    pub center0: Vec3,
+    pub center1: Vec3,
+    pub time0: f64,
+    pub time1: f64,
+    pub radius: f64,
+    pub material: MaterialEnum,
+}
+
+impl MovingSphere {
+    pub fn center(&self, time: f64) -> Vec3 {
+        if self.time1 <= self.time0 {
+            return self.center0;
+        }
+        let s = ((time - self.time0) / (self.time1 - self.time0)).clamp(0.0, 1.0);
+        self.center0 + s * (self.center1 - self.center0)
+    }
+}
+
+impl Hit for MovingSphere {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        hit_sphere(self.center(r.time), self.radius, &self.material, r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(
+            sphere_bounding_box(self.center0, self.radius)
+                .surrounding(sphere_bounding_box(self.center1, self.radius)),
+        )
+    }
+}
@@ -0,0 +1,474 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::hit::HittableList;
+use crate::material::*;
+use crate::mesh::{MeshFace, TriangleMesh};
+use crate::vec3::*;
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            ObjError::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ObjError::Io { source, .. } => Some(source),
+            ObjError::Parse { .. } => None,
+        }
+    }
+}
+
+// Faces from one `g` or `o` statement onward, as a range into the mesh's faces
+#[derive(Debug, Clone)]
+pub struct ObjGroup {
+    pub name: String,
+    pub faces: Range<usize>,
+}
+
+pub struct ObjModel {
+    pub mesh: Arc<TriangleMesh>,
+    pub groups: Vec<ObjGroup>,
+}
+
+impl ObjModel {
+    pub fn load(path: impl AsRef<Path>) -> Result<ObjModel, ObjError> {
+        let path = path.as_ref();
+        let lines = read_lines(path)?;
+        let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+        ObjParser::new(path, base_dir).parse(lines)
+    }
+
+    pub fn hittables(&self) -> HittableList {
+        TriangleMesh::triangles(&self.mesh)
+    }
+
+    // Hittables for the faces of every group called `name`
+    pub fn group_hittables(&self, name: &str) -> HittableList {
+        self.groups
+            .iter()
+            .filter(|group| group.name == name)
+            .flat_map(|group| TriangleMesh::triangles_in(&self.mesh, group.faces.clone()))
+            .collect()
+    }
+}
+
+pub fn load_obj(path: impl AsRef<Path>) -> Result<HittableList, ObjError> {
+    Ok(ObjModel::load(path)?.hittables())
+}
+
+fn read_lines(path: &Path) -> Result<Vec<String>, ObjError> {
+    let io_error = |source| ObjError::Io {
+        path: path.to_path_buf(),
+        source,
+    };
+    let file = File::open(path).map_err(io_error)?;
+    BufReader::new(file)
+        .lines()
+        .collect::<Result<_, _>>()
+        .map_err(io_error)
+}
+
+fn parse_error(path: &Path, line: usize, message: impl Into<String>) -> ObjError {
+    ObjError::Parse {
+        path: path.to_path_buf(),
+        line,
+        message: message.into(),
+    }
+}
+
+// Parses exactly `N` floats, or at least `min` of them with the rest
+// filled from `defaults`
+fn parse_floats<const N: usize>(
+    tokens: &[&str],
+    min: usize,
+    defaults: [f64; N],
+    path: &Path,
+    line: usize,
+) -> Result<[f64; N], ObjError> {
+    if tokens.len() < min {
+        return Err(parse_error(
+            path,
+            line,
+            format!("expected at least {} numbers, found {}", min, tokens.len()),
+        ));
+    }
+    let mut values = defaults;
+    for (value, token) in values.iter_mut().zip(tokens) {
+        *value = token
+            .parse()
+            .map_err(|_| parse_error(path, line, format!("invalid number '{}'", token)))?;
+    }
+    Ok(values)
+}
+
+fn parse_vec3(tokens: &[&str], path: &Path, line: usize) -> Result<Vec3, ObjError> {
+    let [x, y, z] = parse_floats(tokens, 3, [0.0; 3], path, line)?;
+    Ok(Vec3 { x, y, z })
+}
+
+// Material description read from an MTL file
+#[derive(Debug, Clone)]
+pub struct MtlMaterial {
+    pub diffuse: Vec3,
+    pub specular: Vec3,
+    pub emission: Vec3,
+    pub shininess: f64,
+    pub ior: Option<f64>,
+    pub dissolve: f64,
+    pub illum: u32,
+}
+
+impl Default for MtlMaterial {
+    fn default() -> Self {
+        MtlMaterial {
+            diffuse: Vec3 {
+                x: 0.5,
+                y: 0.5,
+                z: 0.5,
+            },
+            specular: Vec3::zeros(),
+            emission: Vec3::zeros(),
+            shininess: 0.0,
+            ior: None,
+            dissolve: 1.0,
+            illum: 2,
+        }
+    }
+}
+
+impl MtlMaterial {
+    // Emissive materials become DiffuseLight, transparent ones Dielectric,
+    // mirror-like ones Metal and everything else Lambertian
+    pub fn to_material(&self) -> MaterialEnum {
+        if self.emission.x > 0.0 || self.emission.y > 0.0 || self.emission.z > 0.0 {
+            return DiffuseLight {
+                emit: self.emission,
+            }
+            .into();
+        }
+
+        let transparent = self.dissolve < 1.0 || matches!(self.illum, 4 | 6 | 7 | 9);
+        let max_specular = self.specular.x.max(self.specular.y).max(self.specular.z);
+        let max_diffuse = self.diffuse.x.max(self.diffuse.y).max(self.diffuse.z);
+        let reflective = self.illum == 3 || (max_specular > 0.0 && max_specular >= max_diffuse);
+
+        if transparent {
+            Dielectric {
+                ir: self.ior.unwrap_or(1.5),
+            }
+            .into()
+        } else if reflective {
+            // Phong exponent to roughness, sqrt(2 / (Ns + 2))
+            let fuzz = (2.0 / (self.shininess.max(0.0) + 2.0)).sqrt().min(1.0);
+            Metal {
+                albedo: self.specular.into(),
+                fuzz,
+            }
+            .into()
+        } else {
+            Lambertian {
+                albedo: self.diffuse.into(),
+            }
+            .into()
+        }
+    }
+}
+
+pub fn load_mtl(path: impl AsRef<Path>) -> Result<HashMap<String, MtlMaterial>, ObjError> {
+    let path = path.as_ref();
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, MtlMaterial)> = None;
+
+    for (index, line) in read_lines(path)?.iter().enumerate() {
+        let line_number = index + 1;
+        let tokens: Vec<&str> = line.split('#').next().unwrap_or("").split_whitespace().collect();
+        let Some((&keyword, args)) = tokens.split_first() else {
+            continue;
+        };
+
+        if keyword == "newmtl" {
+            if args.is_empty() {
+                return Err(parse_error(path, line_number, "newmtl without a name"));
+            }
+            if let Some((name, material)) = current.take() {
+                materials.insert(name, material);
+            }
+            current = Some((args.join(" "), MtlMaterial::default()));
+            continue;
+        }
+
+        let Some((_, material)) = current.as_mut() else {
+            return Err(parse_error(
+                path,
+                line_number,
+                format!("'{}' before any newmtl", keyword),
+            ));
+        };
+
+        match keyword {
+            "Kd" => material.diffuse = parse_vec3(args, path, line_number)?,
+            "Ks" => material.specular = parse_vec3(args, path, line_number)?,
+            "Ke" => material.emission = parse_vec3(args, path, line_number)?,
+            "Ns" => material.shininess = parse_floats(args, 1, [0.0], path, line_number)?[0],
+            "Ni" => material.ior = Some(parse_floats(args, 1, [0.0], path, line_number)?[0]),
+            "d" => material.dissolve = parse_floats(args, 1, [0.0], path, line_number)?[0],
+            "Tr" => material.dissolve = 1.0 - parse_floats(args, 1, [0.0], path, line_number)?[0],
+            "illum" => {
+                material.illum = args.first().and_then(|s| s.parse().ok()).ok_or_else(|| {
+                    parse_error(path, line_number, "illum expects an integer")
+                })?
+            }
+            // Texture maps and other statements aren't supported and are skipped
+            _ => (),
+        }
+    }
+
+    if let Some((name, material)) = current {
+        materials.insert(name, material);
+    }
+    Ok(materials)
+}
+
+struct ObjParser<'a> {
+    path: &'a Path,
+    base_dir: &'a Path,
+    positions: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<[f64; 2]>,
+    faces: Vec<MeshFace>,
+    library: HashMap<String, MtlMaterial>,
+    materials: Vec<MaterialEnum>,
+    material_indices: HashMap<String, usize>,
+    current_material: Option<usize>,
+    groups: Vec<ObjGroup>,
+}
+
+// Position, texture coordinate and normal indices of one face corner
+type VertexRef = (usize, Option<usize>, Option<usize>);
+
+impl<'a> ObjParser<'a> {
+    fn new(path: &'a Path, base_dir: &'a Path) -> ObjParser<'a> {
+        ObjParser {
+            path,
+            base_dir,
+            positions: Vec::new(),
+            normals: Vec::new(),
+            uvs: Vec::new(),
+            faces: Vec::new(),
+            library: HashMap::new(),
+            materials: Vec::new(),
+            material_indices: HashMap::new(),
+            current_material: None,
+            groups: Vec::new(),
+        }
+    }
+
+    fn parse(mut self, lines: Vec<String>) -> Result<ObjModel, ObjError> {
+        for (index, line) in lines.iter().enumerate() {
+            let line_number = index + 1;
+            let tokens: Vec<&str> = line.split('#').next().unwrap_or("").split_whitespace().collect();
+            let Some((&keyword, args)) = tokens.split_first() else {
+                continue;
+            };
+
+            match keyword {
+                "v" => {
+                    let position = parse_vec3(args, self.path, line_number)?;
+                    self.positions.push(position);
+                }
+                "vn" => {
+                    let normal = parse_vec3(args, self.path, line_number)?;
+                    self.normals.push(normal);
+                }
+                "vt" => {
+                    let [u, v] = parse_floats(args, 1, [0.0; 2], self.path, line_number)?;
+                    self.uvs.push([u, v]);
+                }
+                "f" => self.parse_face(args, line_number)?,
+                "g" | "o" => {
+                    self.close_group();
+                    self.groups.push(ObjGroup {
+                        name: args.join(" "),
+                        faces: self.faces.len()..self.faces.len(),
+                    });
+                }
+                "mtllib" => {
+                    for file in args {
+                        let library = load_mtl(self.base_dir.join(file))?;
+                        self.library.extend(library);
+                    }
+                }
+                "usemtl" => {
+                    let name = args.join(" ");
+                    self.current_material = Some(self.material_index(&name, line_number)?);
+                }
+                // Smoothing groups, lines, points and other statements are ignored
+                _ => (),
+            }
+        }
+        self.close_group();
+
+        Ok(ObjModel {
+            mesh: Arc::new(TriangleMesh {
+                positions: self.positions,
+                normals: self.normals,
+                uvs: self.uvs,
+                faces: self.faces,
+                materials: self.materials,
+            }),
+            groups: self.groups,
+        })
+    }
+
+    fn close_group(&mut self) {
+        if let Some(group) = self.groups.last_mut() {
+            group.faces.end = self.faces.len();
+        }
+    }
+
+    fn material_index(&mut self, name: &str, line_number: usize) -> Result<usize, ObjError> {
+        if let Some(&index) = self.material_indices.get(name) {
+            return Ok(index);
+        }
+        let description = self.library.get(name).ok_or_else(|| {
+            parse_error(self.path, line_number, format!("unknown material '{}'", name))
+        })?;
+        self.materials.push(description.to_material());
+        let index = self.materials.len() - 1;
+        self.material_indices.insert(name.to_string(), index);
+        Ok(index)
+    }
+
+    // Faces without usemtl share a default grey Lambertian
+    fn default_material(&mut self) -> usize {
+        if let Some(&index) = self.material_indices.get("") {
+            return index;
+        }
+        self.materials.push(MtlMaterial::default().to_material());
+        let index = self.materials.len() - 1;
+        self.material_indices.insert(String::new(), index);
+        index
+    }
+
+    // OBJ indices are 1-based, negative values count back from the end
+    fn resolve_index(
+        &self,
+        token: &str,
+        count: usize,
+        kind: &str,
+        line_number: usize,
+    ) -> Result<usize, ObjError> {
+        let index: i64 = token.parse().map_err(|_| {
+            parse_error(self.path, line_number, format!("invalid {} index '{}'", kind, token))
+        })?;
+        let resolved = if index < 0 {
+            count as i64 + index
+        } else {
+            index - 1
+        };
+        if resolved < 0 || resolved >= count as i64 {
+            return Err(parse_error(
+                self.path,
+                line_number,
+                format!("{} index {} out of range (have {})", kind, index, count),
+            ));
+        }
+        Ok(resolved as usize)
+    }
+
+    fn parse_vertex_ref(&self, token: &str, line_number: usize) -> Result<VertexRef, ObjError> {
+        let mut parts = token.split('/');
+        let position = self.resolve_index(
+            parts.next().unwrap_or(""),
+            self.positions.len(),
+            "vertex",
+            line_number,
+        )?;
+        let uv = match parts.next() {
+            Some(s) if !s.is_empty() => {
+                Some(self.resolve_index(s, self.uvs.len(), "texture coordinate", line_number)?)
+            }
+            _ => None,
+        };
+        let normal = match parts.next() {
+            Some(s) if !s.is_empty() => {
+                Some(self.resolve_index(s, self.normals.len(), "normal", line_number)?)
+            }
+            _ => None,
+        };
+        if parts.next().is_some() {
+            return Err(parse_error(
+                self.path,
+                line_number,
+                format!("malformed face vertex '{}'", token),
+            ));
+        }
+        Ok((position, uv, normal))
+    }
+
+    // Polygons are triangulated as a fan around their first vertex
+    fn parse_face(&mut self, args: &[&str], line_number: usize) -> Result<(), ObjError> {
+        if args.len() < 3 {
+            return Err(parse_error(
+                self.path,
+                line_number,
+                format!("face needs at least 3 vertices, found {}", args.len()),
+            ));
+        }
+        let corners = args
+            .iter()
+            .map(|token| self.parse_vertex_ref(token, line_number))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let material = match self.current_material {
+            Some(index) => index,
+            None => self.default_material(),
+        };
+
+        for i in 1..corners.len() - 1 {
+            let triangle = [corners[0], corners[i], corners[i + 1]];
+            let all = |select: fn(&VertexRef) -> Option<usize>| -> Option<[usize; 3]> {
+                Some([
+                    select(&triangle[0])?,
+                    select(&triangle[1])?,
+                    select(&triangle[2])?,
+                ])
+            };
+            self.faces.push(MeshFace {
+                vertices: [triangle[0].0, triangle[1].0, triangle[2].0],
+                uvs: all(|corner| corner.1),
+                normals: all(|corner| corner.2),
+                material,
+            });
+        }
+        Ok(())
+    }
+}
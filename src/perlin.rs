@@ -0,0 +1,107 @@
+use rand::prelude::{SliceRandom, SeedableRng};
+
+use crate::uniform_wrapper::*;
+use crate::vec3::*;
+
+const POINT_COUNT: usize = 256;
+
+// Gradient noise with random unit vectors at the lattice points,
+// as in Ray Tracing: The Next Week
+pub struct Perlin {
+    gradients: Vec<Vec3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Perlin {
+        let mut unigen_neg1_1 = UniGenNeg1_1::seeded(stream_seed(seed, &[0]));
+        let gradients = (0..POINT_COUNT)
+            .map(|_| Vec3::random_unit_vector(&mut unigen_neg1_1))
+            .collect();
+
+        let mut rng = UniRng::seed_from_u64(stream_seed(seed, &[1]));
+        let mut permutation = || {
+            let mut perm: Vec<usize> = (0..POINT_COUNT).collect();
+            perm.shuffle(&mut rng);
+            perm
+        };
+
+        Perlin {
+            gradients,
+            perm_x: permutation(),
+            perm_y: permutation(),
+            perm_z: permutation(),
+        }
+    }
+
+    // Roughly in [-1, 1]
+    pub fn noise(&self, p: Vec3) -> f64 {
+        let u = p.x - p.x.floor();
+        let v = p.y - p.y.floor();
+        let w = p.z - p.z.floor();
+
+        let i = p.x.floor() as i64;
+        let j = p.y.floor() as i64;
+        let k = p.z.floor() as i64;
+
+        let mut corners = [[[Vec3::zeros(); 2]; 2]; 2];
+        for (di, plane) in corners.iter_mut().enumerate() {
+            for (dj, row) in plane.iter_mut().enumerate() {
+                for (dk, corner) in row.iter_mut().enumerate() {
+                    let index = self.perm_x[wrap(i + di as i64)]
+                        ^ self.perm_y[wrap(j + dj as i64)]
+                        ^ self.perm_z[wrap(k + dk as i64)];
+                    *corner = self.gradients[index];
+                }
+            }
+        }
+
+        trilinear_interpolate(&corners, u, v, w)
+    }
+
+    // Sum of `depth` octaves of noise, each at double the frequency and
+    // half the weight of the previous one
+    pub fn turbulence(&self, p: Vec3, depth: u32) -> f64 {
+        let mut accum = 0.0;
+        let mut point = p;
+        let mut weight = 1.0;
+        for _ in 0..depth {
+            accum += weight * self.noise(point);
+            weight *= 0.5;
+            point *= 2.0;
+        }
+        accum.abs()
+    }
+}
+
+fn wrap(i: i64) -> usize {
+    i.rem_euclid(POINT_COUNT as i64) as usize
+}
+
+// Hermite smoothing avoids grid artifacts from plain linear interpolation
+fn trilinear_interpolate(corners: &[[[Vec3; 2]; 2]; 2], u: f64, v: f64, w: f64) -> f64 {
+    let uu = u * u * (3.0 - 2.0 * u);
+    let vv = v * v * (3.0 - 2.0 * v);
+    let ww = w * w * (3.0 - 2.0 * w);
+
+    let mut accum = 0.0;
+    for (i, plane) in corners.iter().enumerate() {
+        for (j, row) in plane.iter().enumerate() {
+            for (k, gradient) in row.iter().enumerate() {
+                let (fi, fj, fk) = (i as f64, j as f64, k as f64);
+                let weight = Vec3 {
+                    x: u - fi,
+                    y: v - fj,
+                    z: w - fk,
+                };
+                accum += (fi * uu + (1.0 - fi) * (1.0 - uu))
+                    * (fj * vv + (1.0 - fj) * (1.0 - vv))
+                    * (fk * ww + (1.0 - fk) * (1.0 - ww))
+                    * gradient.dot(weight);
+            }
+        }
+    }
+    accum
+}
@@ -0,0 +1,320 @@
+use std::collections::BTreeMap;
+
+use rand::prelude::{Rng, SeedableRng};
+
+use crate::scene::*;
+use crate::uniform_wrapper::*;
+use crate::vec3::*;
+
+pub const PRESET_NAMES: &[&str] = &["random", "bouncing", "cornell"];
+
+// Presets with random content are generated from `seed`, or 0 without one,
+// so every run gives the same scene
+pub fn preset(name: &str, seed: Option<u64>) -> Option<SceneDescription> {
+    let seed = seed.unwrap_or(0);
+    match name {
+        "random" => Some(random_scene(seed)),
+        "bouncing" => Some(bouncing_spheres(seed)),
+        "cornell" => Some(cornell_box()),
+        _ => None,
+    }
+}
+
+// The cover image of Ray Tracing in One Weekend
+pub fn random_scene(seed: u64) -> SceneDescription {
+    let mut materials = BTreeMap::new();
+    let mut objects = Vec::new();
+
+    let mut unigen0_1 = UniGen0_1::seeded(stream_seed(seed, &[0]));
+    let mut rng = UniRng::seed_from_u64(stream_seed(seed, &[1]));
+
+    materials.insert(
+        "ground".to_string(),
+        MaterialDescription::Lambertian {
+            albedo: Vec3 {
+                x: 0.5,
+                y: 0.5,
+                z: 0.5,
+            }
+            .into(),
+        },
+    );
+    objects.push(ObjectDescription::Sphere {
+        center: Vec3 {
+            x: 0.0,
+            y: -1000.0,
+            z: 0.0,
+        },
+        radius: 1000.0,
+        material: "ground".to_string(),
+    });
+
+    materials.insert("glass".to_string(), MaterialDescription::Dielectric { ir: 1.5 });
+
+    let scene_center = Vec3 {
+        x: 4.0,
+        y: 0.2,
+        z: 0.0,
+    };
+
+    for a in -11..11 {
+        for b in -11..11 {
+            let choose_mat: f64 = rng.gen_range(0.0..1.0);
+            let center = Vec3 {
+                x: a as f64 + rng.gen_range(0.0..0.9),
+                y: 0.2,
+                z: b as f64 + rng.gen_range(0.0..0.9),
+            };
+
+            if (center - scene_center).length() > 0.9 {
+                let name = format!("small_{:03}", objects.len());
+                let material = if choose_mat < 0.8 {
+                    let albedo = Vec3::random_unit_vector(&mut unigen0_1)
+                        * Vec3::random_unit_vector(&mut unigen0_1);
+                    materials.insert(name.clone(), MaterialDescription::Lambertian { albedo: albedo.into() });
+                    name
+                } else if choose_mat < 0.95 {
+                    let albedo = Vec3::random_unit_vector(&mut UniGenUntyped::seeded(0.5, 1.0, rng.gen()));
+                    let fuzz = rng.gen_range(0.0..0.5);
+                    materials.insert(name.clone(), MaterialDescription::Metal { albedo: albedo.into(), fuzz });
+                    name
+                } else {
+                    "glass".to_string()
+                };
+
+                objects.push(ObjectDescription::Sphere {
+                    center,
+                    radius: 0.2,
+                    material,
+                });
+            }
+        }
+    }
+
+    objects.push(ObjectDescription::Sphere {
+        center: Vec3 {
+            x: 0.0,
+            y: 1.0,
+            z: 0.0,
+        },
+        radius: 1.0,
+        material: "glass".to_string(),
+    });
+
+    materials.insert(
+        "big_diffuse".to_string(),
+        MaterialDescription::Lambertian {
+            albedo: Vec3 {
+                x: 0.4,
+                y: 0.2,
+                z: 0.1,
+            }
+            .into(),
+        },
+    );
+    objects.push(ObjectDescription::Sphere {
+        center: Vec3 {
+            x: -4.0,
+            y: 1.0,
+            z: 0.0,
+        },
+        radius: 1.0,
+        material: "big_diffuse".to_string(),
+    });
+
+    materials.insert(
+        "big_metal".to_string(),
+        MaterialDescription::Metal {
+            albedo: Vec3 {
+                x: 0.7,
+                y: 0.6,
+                z: 0.5,
+            }
+            .into(),
+            fuzz: 0.0,
+        },
+    );
+    objects.push(ObjectDescription::Sphere {
+        center: Vec3 {
+            x: 4.0,
+            y: 1.0,
+            z: 0.0,
+        },
+        radius: 1.0,
+        material: "big_metal".to_string(),
+    });
+
+    SceneDescription {
+        camera: CameraDescription {
+            projection: Default::default(),
+            look_from: Vec3 {
+                x: 13.0,
+                y: 2.0,
+                z: 3.0,
+            },
+            look_at: Vec3::zeros(),
+            v_up: Vec3 {
+                x: 0.0,
+                y: 1.0,
+                z: 0.0,
+            },
+            v_fov: 20.0,
+            aperture: 0.10,
+            focus_dist: Some(10.0),
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+        },
+        image: ImageDescription {
+            width: 500,
+            height: None,
+            aspect_ratio: Some(16.0 / 9.0),
+            samples_per_pixel: 50,
+            max_depth: 50,
+            roulette_depth: 3,
+            seed,
+            tone_map: Default::default(),
+            ..Default::default()
+        },
+        background: BackgroundDescription::Sky,
+        materials,
+        objects,
+        prototypes: BTreeMap::new(),
+    }
+}
+
+// The random scene with the small diffuse spheres bouncing upwards while the
+// shutter is open, as in Ray Tracing: The Next Week
+pub fn bouncing_spheres(seed: u64) -> SceneDescription {
+    let mut scene = random_scene(seed);
+    let mut rng = UniRng::seed_from_u64(stream_seed(scene.image.seed, &[2]));
+
+    for object in &mut scene.objects {
+        if let ObjectDescription::Sphere {
+            center,
+            radius,
+            material,
+        } = object
+        {
+            let diffuse = matches!(
+                scene.materials.get(material.as_str()),
+                Some(MaterialDescription::Lambertian { .. })
+            );
+            if diffuse && material.starts_with("small_") {
+                let bounce = Vec3 {
+                    x: 0.0,
+                    y: rng.gen_range(0.0..0.5),
+                    z: 0.0,
+                };
+                *object = ObjectDescription::MovingSphere {
+                    center0: *center,
+                    center1: *center + bounce,
+                    time0: 0.0,
+                    time1: 1.0,
+                    radius: *radius,
+                    material: material.clone(),
+                };
+            }
+        }
+    }
+
+    scene.camera.shutter_open = 0.0;
+    scene.camera.shutter_close = 1.0;
+    scene
+}
+
+// Parallelogram with one corner at `corner` and edges `u` and `v`, as two triangles
+fn quad(corner: Vec3, u: Vec3, v: Vec3, material: &str) -> [ObjectDescription; 2] {
+    [
+        ObjectDescription::Triangle {
+            vertices: [corner, corner + u, corner + u + v],
+            material: material.to_string(),
+        },
+        ObjectDescription::Triangle {
+            vertices: [corner, corner + u + v, corner + v],
+            material: material.to_string(),
+        },
+    ]
+}
+
+// Axis-aligned box between two opposite corners
+fn axis_aligned_box(a: Vec3, b: Vec3, material: &str) -> Vec<ObjectDescription> {
+    let d = b - a;
+    let dx = Vec3 { x: d.x, y: 0.0, z: 0.0 };
+    let dy = Vec3 { x: 0.0, y: d.y, z: 0.0 };
+    let dz = Vec3 { x: 0.0, y: 0.0, z: d.z };
+
+    [
+        quad(a, dx, dy, material),
+        quad(a + dz, dx, dy, material),
+        quad(a, dy, dz, material),
+        quad(a + dx, dy, dz, material),
+        quad(a, dx, dz, material),
+        quad(a + dy, dx, dz, material),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+// Lit only by a small ceiling light, so most of the image is indirect light
+pub fn cornell_box() -> SceneDescription {
+    let color = |x, y, z| Vec3 { x, y, z };
+    let mut materials = BTreeMap::new();
+    materials.insert(
+        "red".to_string(),
+        MaterialDescription::Lambertian { albedo: color(0.65, 0.05, 0.05).into() },
+    );
+    materials.insert(
+        "white".to_string(),
+        MaterialDescription::Lambertian { albedo: color(0.73, 0.73, 0.73).into() },
+    );
+    materials.insert(
+        "green".to_string(),
+        MaterialDescription::Lambertian { albedo: color(0.12, 0.45, 0.15).into() },
+    );
+    materials.insert(
+        "light".to_string(),
+        MaterialDescription::DiffuseLight { emit: color(15.0, 15.0, 15.0) },
+    );
+
+    let zero = Vec3::zeros();
+    let size = 555.0;
+    let mut objects = Vec::new();
+    objects.extend(quad(color(size, 0.0, 0.0), color(0.0, size, 0.0), color(0.0, 0.0, size), "green"));
+    objects.extend(quad(zero, color(0.0, size, 0.0), color(0.0, 0.0, size), "red"));
+    objects.extend(quad(zero, color(size, 0.0, 0.0), color(0.0, 0.0, size), "white"));
+    objects.extend(quad(color(0.0, size, 0.0), color(size, 0.0, 0.0), color(0.0, 0.0, size), "white"));
+    objects.extend(quad(color(0.0, 0.0, size), color(size, 0.0, 0.0), color(0.0, size, 0.0), "white"));
+    objects.extend(quad(color(213.0, 554.0, 227.0), color(130.0, 0.0, 0.0), color(0.0, 0.0, 105.0), "light"));
+    objects.extend(axis_aligned_box(color(130.0, 0.0, 65.0), color(295.0, 165.0, 230.0), "white"));
+    objects.extend(axis_aligned_box(color(265.0, 0.0, 295.0), color(430.0, 330.0, 460.0), "white"));
+
+    SceneDescription {
+        camera: CameraDescription {
+            projection: Default::default(),
+            look_from: color(278.0, 278.0, -800.0),
+            look_at: color(278.0, 278.0, 0.0),
+            v_up: color(0.0, 1.0, 0.0),
+            v_fov: 40.0,
+            aperture: 0.0,
+            focus_dist: None,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+        },
+        image: ImageDescription {
+            width: 400,
+            height: Some(400),
+            aspect_ratio: None,
+            samples_per_pixel: 100,
+            max_depth: 50,
+            roulette_depth: 3,
+            seed: 0,
+            tone_map: Default::default(),
+            ..Default::default()
+        },
+        background: BackgroundDescription::Solid(Vec3::zeros()),
+        materials,
+        objects,
+        prototypes: BTreeMap::new(),
+    }
+}
@@ -3,7 +3,9 @@ use crate::vec3::Vec3;
 #[derive(Debug, Clone, Copy)]
 pub struct Ray {
     pub origin: Vec3,
-    pub direction: Vec3
+    pub direction: Vec3,
+    // When the ray was sent, between the camera's shutter open and close times
+    pub time: f64
 }
 
 pub trait VecAt {
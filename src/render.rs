@@ -0,0 +1,324 @@
+use rayon::prelude::*;
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::background::Background;
+use crate::camera::{Camera, CameraEnum};
+use crate::checkpoint::{LuminanceStats, RenderState};
+use crate::color::ray_color_vec;
+use crate::filter::Filter;
+use crate::framebuffer::Framebuffer;
+use crate::hit::Hittable;
+use crate::lights::LightList;
+use crate::medium::MediumList;
+use crate::scene::Scene;
+use crate::settings::RenderSettings;
+use crate::tiles::{tiles, Tile};
+use crate::tonemap;
+use crate::sampler::Sampler;
+use crate::uniform_wrapper::UniRng;
+use crate::vec3::*;
+
+pub struct Renderer {
+    pub settings: RenderSettings,
+}
+
+// A pixel's running totals after a pass
+struct PixelUpdate {
+    count: u32,
+    luminance: LuminanceStats,
+    rng: UniRng,
+}
+
+// Filtered samples from one tile, over the tile and the margin around it
+// that its samples reach into. The tile's own pixels start from the totals
+// so far and replace them; the margin starts from zero and is added to them,
+// as neighbouring tiles splat into it too.
+struct SplatBuffer {
+    region: Tile,
+    sums: Vec<Vec3>,
+    weights: Vec<f64>,
+}
+
+impl SplatBuffer {
+    fn new(tile: &Tile, margin: u32, state: &RenderState) -> SplatBuffer {
+        let region = tile.expanded(margin, state.width, state.height);
+        let pixel_count = region.width as usize * region.height as usize;
+        let mut buffer = SplatBuffer {
+            region,
+            sums: vec![Vec3::zeros(); pixel_count],
+            weights: vec![0.0; pixel_count],
+        };
+        for j in tile.y0..tile.y0 + tile.height {
+            for i in tile.x0..tile.x0 + tile.width {
+                let index = buffer.index(i, j);
+                let state_index = j as usize * state.width as usize + i as usize;
+                buffer.sums[index] = state.sums[state_index];
+                buffer.weights[index] = state.weights[state_index];
+            }
+        }
+        buffer
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        (y - self.region.y0) as usize * self.region.width as usize + (x - self.region.x0) as usize
+    }
+
+    // Spreads a sample at (x, y) in pixel units over the pixels its filter reaches
+    fn splat(&mut self, filter: &Filter, x: f64, y: f64, color: Vec3) {
+        let rows = filter.pixel_range(y, self.region.y0 + self.region.height);
+        let columns = filter.pixel_range(x, self.region.x0 + self.region.width);
+        for j in rows {
+            for i in columns.clone() {
+                let weight = filter.evaluate(x - (i as f64 + 0.5), y - (j as f64 + 0.5));
+                if weight != 0.0 {
+                    let index = self.index(i, j);
+                    self.sums[index] += weight * color;
+                    self.weights[index] += weight;
+                }
+            }
+        }
+    }
+}
+
+impl Renderer {
+    pub fn new(settings: RenderSettings) -> Renderer {
+        Renderer { settings }
+    }
+
+    // Renders with the scene's own camera and background, but this renderer's settings
+    pub fn render_scene(&self, scene: &Scene) -> Framebuffer {
+        self.render(&scene.world, &scene.camera, &scene.background)
+    }
+
+    pub fn render(
+        &self,
+        world: &Hittable,
+        cam: &CameraEnum,
+        background: &Background,
+    ) -> Framebuffer {
+        let mut state = RenderState::new(&self.settings);
+        let Ok(()) = self.render_progressive(world, cam, background, &mut state, |_| {
+            Ok::<(), Infallible>(())
+        });
+        state.to_framebuffer()
+    }
+
+    // Number of samples `pixel` should have after the next pass: up to
+    // samples_per_pixel everywhere, then more for pixels that are still
+    // noisy if sampling is adaptive
+    fn pass_target(&self, state: &RenderState, pixel: usize) -> u32 {
+        let samples_per_pixel = self.settings.samples_per_pixel;
+        let samples_per_pass = self
+            .settings
+            .samples_per_pass
+            .unwrap_or(samples_per_pixel)
+            .max(1);
+
+        let count = state.counts[pixel];
+        if count < samples_per_pixel {
+            return (count + samples_per_pass).min(samples_per_pixel);
+        }
+        match &self.settings.adaptive {
+            Some(adaptive)
+                if count < adaptive.max_samples_per_pixel
+                    && state.luminance[pixel].relative_error(count) > adaptive.threshold =>
+            {
+                (count + samples_per_pass).min(adaptive.max_samples_per_pixel)
+            }
+            _ => count,
+        }
+    }
+
+    // Pixels the next pass would add samples to
+    pub fn pixels_remaining(&self, state: &RenderState) -> usize {
+        (0..state.counts.len())
+            .filter(|&pixel| self.pass_target(state, pixel) > state.counts[pixel])
+            .count()
+    }
+
+    // Adds samples to `state` in passes of samples_per_pass until every pixel
+    // has samples_per_pixel, or has converged if sampling is adaptive,
+    // calling `after_pass` after each one. Stops early if `after_pass` fails.
+    pub fn render_progressive<E>(
+        &self,
+        world: &Hittable,
+        cam: &CameraEnum,
+        background: &Background,
+        state: &mut RenderState,
+        mut after_pass: impl FnMut(&RenderState) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let lights = LightList::from_world(world).with_background(background);
+        let media = MediumList::from_world(world);
+
+        while self.pixels_remaining(state) > 0 {
+            self.render_pass(world, cam, background, &lights, &media, state);
+            after_pass(state)?;
+        }
+        Ok(())
+    }
+
+    // Brings every pixel up to its pass target
+    fn render_pass(
+        &self,
+        world: &Hittable,
+        cam: &CameraEnum,
+        background: &Background,
+        lights: &LightList,
+        media: &MediumList,
+        state: &mut RenderState,
+    ) {
+        let image_width = self.settings.image_width;
+        let image_height = self.settings.image_height;
+        let max_depth = self.settings.max_depth;
+        let roulette_depth = self.settings.roulette_depth;
+        let filter = &self.settings.filter;
+
+        let render_pixel = |i: u32, j: u32, splats: &mut SplatBuffer| {
+            let index = j as usize * image_width as usize + i as usize;
+            let pass_target = self.pass_target(state, index);
+            // Samples depend only on the seed and pixel, never on scheduling
+            let mut sampler = self.settings.sampler.pixel_sampler(
+                self.settings.seed,
+                i,
+                j,
+                self.settings.samples_per_pixel,
+                state.rngs[index].clone(),
+            );
+
+            let mut luminance = state.luminance[index];
+            let count = state.counts[index].max(pass_target);
+            for sample in state.counts[index]..pass_target {
+                sampler.start_sample(sample);
+                let (du, dv) = sampler.get_2d();
+                let (x, y) = (i as f64 + du, j as f64 + dv);
+                let u = x / image_width as f64;
+                let v = 1.0 - y / image_height as f64;
+
+                let sample_color = match cam.get_ray(u, v, &mut sampler) {
+                    Some(r) => ray_color_vec(&r, world, lights, media, background, &mut sampler, max_depth, roulette_depth),
+                    None => Vec3::zeros(),
+                };
+                splats.splat(filter, x, y, sample_color);
+                luminance.add(tonemap::luminance(sample_color), sample + 1);
+            }
+
+            PixelUpdate {
+                count,
+                luminance,
+                rng: sampler
+                    .into_rng()
+                    .unwrap_or_else(|| state.rngs[index].clone()),
+            }
+        };
+
+        // Each thread takes the next tile in order and renders it into a
+        // buffer of its own; nothing is shared until the tiles are copied
+        // into the state at the end. Samples splat into the neighbouring
+        // pixels within the filter's reach, so each buffer covers that much
+        // more than its tile.
+        let tiles = tiles(
+            image_width,
+            image_height,
+            self.settings.tile_size,
+            self.settings.tile_order,
+        );
+        let margin = filter.margin();
+        let next_tile = AtomicUsize::new(0);
+        let mut rendered: Vec<(Tile, Vec<PixelUpdate>, SplatBuffer)> =
+            (0..rayon::current_num_threads())
+                .into_par_iter()
+                .flat_map_iter(|_| {
+                    let mut done = Vec::new();
+                    while let Some(&tile) = tiles.get(next_tile.fetch_add(1, Ordering::Relaxed)) {
+                        let mut splats = SplatBuffer::new(&tile, margin, state);
+                        let pixels = (tile.y0..tile.y0 + tile.height)
+                            .flat_map(|j| (tile.x0..tile.x0 + tile.width).map(move |i| (i, j)))
+                            .map(|(i, j)| render_pixel(i, j, &mut splats))
+                            .collect();
+                        done.push((tile, pixels, splats));
+                    }
+                    done
+                })
+                .collect();
+
+        // Margins are added in a fixed order, whichever thread finished first
+        rendered.sort_by_key(|(tile, _, _)| (tile.y0, tile.x0));
+        for (tile, pixels, splats) in &rendered {
+            let rows = (tile.y0..tile.y0 + tile.height)
+                .flat_map(|j| (tile.x0..tile.x0 + tile.width).map(move |i| (i, j)));
+            for ((i, j), update) in rows.zip(pixels) {
+                let index = j as usize * image_width as usize + i as usize;
+                let splat_index = splats.index(i, j);
+                state.sums[index] = splats.sums[splat_index];
+                state.weights[index] = splats.weights[splat_index];
+                state.counts[index] = update.count;
+                state.luminance[index] = update.luminance;
+                state.rngs[index] = update.rng.clone();
+            }
+        }
+        for (tile, _, splats) in &rendered {
+            let region = splats.region;
+            for j in region.y0..region.y0 + region.height {
+                for i in region.x0..region.x0 + region.width {
+                    if !tile.contains(i, j) {
+                        let index = j as usize * image_width as usize + i as usize;
+                        let splat_index = splats.index(i, j);
+                        state.sums[index] += splats.sums[splat_index];
+                        state.weights[index] += splats.weights[splat_index];
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::filter::Filter;
+    use crate::presets;
+
+    fn render_state(filter: Filter, tile_size: u32) -> RenderState {
+        let mut description = presets::preset("cornell", None).unwrap();
+        description.image.width = 24;
+        description.image.height = Some(20);
+        description.image.samples_per_pixel = 2;
+        description.image.max_depth = 4;
+        description.image.filter = filter;
+        description.image.tile_size = tile_size;
+        let scene = description.build(Path::new("")).unwrap();
+
+        let renderer = Renderer::new(scene.settings.clone());
+        let mut state = RenderState::new(&renderer.settings);
+        let Ok(()) = renderer.render_progressive(
+            &scene.world,
+            &scene.camera,
+            &scene.background,
+            &mut state,
+            |_| Ok::<(), Infallible>(()),
+        );
+        state
+    }
+
+    // Tiles only change the order the margins are added in, so the totals
+    // agree up to rounding; a margin lost or added twice would be far off
+    #[test]
+    fn tile_size_does_not_change_the_image() {
+        for filter in [Filter::box_filter(), Filter::gaussian(), Filter::lanczos()] {
+            let reference = render_state(filter, 32);
+            for tile_size in [1, 3, 7, 16] {
+                let state = render_state(filter, tile_size);
+                assert_eq!(state.counts, reference.counts);
+                for (weight, expected) in state.weights.iter().zip(&reference.weights) {
+                    assert!((weight - expected).abs() <= 1e-12 * expected.abs().max(1.0));
+                }
+                for (sum, expected) in state.sums.iter().zip(&reference.sums) {
+                    assert!((*sum - *expected).length() <= 1e-12 * expected.length().max(1.0));
+                }
+            }
+        }
+    }
+}
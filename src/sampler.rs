@@ -0,0 +1,487 @@
+use enum_dispatch::enum_dispatch;
+use serde::{Deserialize, Serialize};
+
+use crate::uniform_wrapper::*;
+
+// Hands out the random numbers for one pixel's samples. Each sample is a
+// point in a high-dimensional unit cube: the camera takes the first
+// dimensions for the pixel position, lens and time, then every bounce takes
+// the next ones for its scattering, light choice and roulette in the order
+// it asks. Loops with no fixed length, like tracking through a medium,
+// shift the later bounces along, but no dimension is ever used twice.
+#[enum_dispatch(SamplerEnum)]
+pub trait Sampler {
+    // Begins the pixel's sample `index`, going back to the first dimension
+    fn start_sample(&mut self, index: u32);
+
+    // Next dimension, in [0, 1)
+    fn get_1d(&mut self) -> f64;
+
+    // Next two dimensions, stratified together where the sampler can
+    fn get_2d(&mut self) -> (f64, f64);
+}
+
+#[enum_dispatch]
+pub enum SamplerEnum {
+    IndependentSampler,
+    StratifiedSampler,
+    HaltonSampler,
+    SobolSampler,
+}
+
+impl SamplerEnum {
+    // Where the pixel's random stream is up to, for samplers that have one
+    pub fn into_rng(self) -> Option<UniRng> {
+        match self {
+            SamplerEnum::IndependentSampler(sampler) => Some(sampler.unigen0_1.into_rng()),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SamplerKind {
+    #[default]
+    Independent,
+    Stratified,
+    Halton,
+    Sobol,
+}
+
+impl SamplerKind {
+    // Sampler for pixel (i, j). `rng` is the pixel's random stream, which
+    // only the independent sampler uses; the others are fixed by the seed,
+    // pixel and sample index. Stratification is over `samples_per_pixel`.
+    pub fn pixel_sampler(
+        self,
+        seed: u64,
+        i: u32,
+        j: u32,
+        samples_per_pixel: u32,
+        rng: UniRng,
+    ) -> SamplerEnum {
+        let seed = stream_seed(seed, &[i as u64, j as u64, 2]);
+        match self {
+            SamplerKind::Independent => IndependentSampler {
+                unigen0_1: UniGen0_1::from_rng(rng),
+            }
+            .into(),
+            SamplerKind::Stratified => StratifiedSampler {
+                seed,
+                strata: samples_per_pixel.max(1),
+                index: 0,
+                dimension: 0,
+            }
+            .into(),
+            SamplerKind::Halton => HaltonSampler {
+                seed,
+                index: 0,
+                dimension: 0,
+            }
+            .into(),
+            SamplerKind::Sobol => SobolSampler {
+                seed,
+                index: 0,
+                dimension: 0,
+            }
+            .into(),
+        }
+    }
+}
+
+const ONE_MINUS_EPSILON: f64 = 1.0 - f64::EPSILON / 2.0;
+
+// Uniform in [0, 1) from 64 random bits
+fn bits_to_unit(bits: u64) -> f64 {
+    (bits >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+}
+
+fn u32_to_unit(bits: u32) -> f64 {
+    bits as f64 * (1.0 / (1u64 << 32) as f64)
+}
+
+// Every sample uniformly random and independent of the others
+pub struct IndependentSampler {
+    pub unigen0_1: UniGen0_1,
+}
+
+impl Sampler for IndependentSampler {
+    fn start_sample(&mut self, _index: u32) {}
+
+    fn get_1d(&mut self) -> f64 {
+        self.unigen0_1.sample()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.unigen0_1.sample(), self.unigen0_1.sample())
+    }
+}
+
+// Splits each dimension into `strata` equal intervals, and each pair of
+// dimensions into a grid of about as many cells, with one jittered sample
+// in each. The order the strata are visited in is shuffled separately for
+// every dimension so dimensions don't correlate. Samples past `strata`
+// start a new, differently shuffled round.
+pub struct StratifiedSampler {
+    pub seed: u64,
+    pub strata: u32,
+    pub index: u32,
+    pub dimension: u32,
+}
+
+impl StratifiedSampler {
+    fn jitter(&self, dimension: u32) -> f64 {
+        bits_to_unit(stream_seed(
+            self.seed,
+            &[dimension as u64, self.index as u64, 1],
+        ))
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_sample(&mut self, index: u32) {
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let dimension = self.dimension;
+        self.dimension += 1;
+
+        let n = self.strata;
+        let round = self.index / n;
+        let shuffle = stream_seed(self.seed, &[dimension as u64, round as u64, 0]) as u32;
+        let stratum = permutation_element(self.index % n, n, shuffle);
+        ((stratum as f64 + self.jitter(dimension)) / n as f64).min(ONE_MINUS_EPSILON)
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let dimension = self.dimension;
+        self.dimension += 2;
+
+        let columns = (self.strata as f64).sqrt().ceil() as u32;
+        let rows = self.strata.div_ceil(columns);
+        let n = columns * rows;
+        let round = self.index / n;
+        let shuffle = stream_seed(self.seed, &[dimension as u64, round as u64, 0]) as u32;
+        let cell = permutation_element(self.index % n, n, shuffle);
+        (
+            (((cell % columns) as f64 + self.jitter(dimension)) / columns as f64)
+                .min(ONE_MINUS_EPSILON),
+            (((cell / columns) as f64 + self.jitter(dimension + 1)) / rows as f64)
+                .min(ONE_MINUS_EPSILON),
+        )
+    }
+}
+
+// Element `i` of a random permutation of 0..n chosen by `seed`, without
+// building the permutation (Kensler, "Correlated Multi-Jittered Sampling")
+fn permutation_element(mut i: u32, n: u32, seed: u32) -> u32 {
+    let mut w = n - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    // Hashes within the next power of two until the result lands below n
+    loop {
+        i ^= seed;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= seed >> 16;
+        i ^= (i & w) >> 4;
+        i ^= seed >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= seed >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | seed >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < n {
+            break;
+        }
+    }
+    // Reduced first, as a sum that wrapped around would repeat elements
+    (i + seed % n) % n
+}
+
+const PRIMES: [u32; 64] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89,
+    97, 101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191,
+    193, 197, 199, 211, 223, 227, 229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281, 283, 293,
+    307, 311,
+];
+
+// The Halton sequence, the radical inverse of the sample index in a
+// different prime base for each dimension. The digits are Owen scrambled
+// per pixel and dimension, which breaks up the correlation between
+// dimensions with large bases and keeps neighbouring pixels from repeating
+// the same pattern. Dimensions past the table of primes fall back to
+// independent random numbers.
+pub struct HaltonSampler {
+    pub seed: u64,
+    pub index: u32,
+    pub dimension: u32,
+}
+
+impl Sampler for HaltonSampler {
+    fn start_sample(&mut self, index: u32) {
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let dimension = self.dimension;
+        self.dimension += 1;
+
+        match PRIMES.get(dimension as usize) {
+            Some(&base) => owen_scrambled_radical_inverse(
+                base,
+                self.index,
+                stream_seed(self.seed, &[dimension as u64]),
+            ),
+            None => bits_to_unit(stream_seed(
+                self.seed,
+                &[dimension as u64, self.index as u64],
+            )),
+        }
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.get_1d(), self.get_1d())
+    }
+}
+
+// Digits of `index` in `base`, mirrored about the radix point, with each
+// digit permuted by a hash of its position and the digits before it.
+// Carries on through the leading zeros until the digits fall below double
+// precision.
+fn owen_scrambled_radical_inverse(base: u32, mut index: u32, seed: u64) -> f64 {
+    let inverse_base = 1.0 / base as f64;
+    let mut value = 0.0;
+    let mut scale = 1.0;
+    // Digits so far, only for hashing; it wraps around past 64 bits
+    let mut prefix = 0u64;
+    let mut position = 0;
+    while 1.0 - (base - 1) as f64 * scale < 1.0 {
+        let next = index / base;
+        let digit_hash = stream_seed(seed, &[position, prefix]) as u32;
+        let digit = permutation_element(index - next * base, base, digit_hash);
+        prefix = prefix.wrapping_mul(base as u64).wrapping_add(digit as u64);
+        position += 1;
+        scale *= inverse_base;
+        value += digit as f64 * scale;
+        index = next;
+    }
+    value.min(ONE_MINUS_EPSILON)
+}
+
+// Sobol points with Owen scrambling (Burley, "Practical Hash-based Owen
+// Scrambling"). Every pair of dimensions uses the first two Sobol
+// dimensions, which together stratify well at every power of two, with the
+// sample order shuffled and the values scrambled by hashes of the pixel and
+// dimension. Scrambling keeps the stratification but removes the
+// correlation between pairs and the structured look of the raw sequence.
+pub struct SobolSampler {
+    pub seed: u64,
+    pub index: u32,
+    pub dimension: u32,
+}
+
+impl SobolSampler {
+    // Index into the sequence after the shuffle for `dimension`, plus two
+    // seeds for scrambling its values
+    fn shuffled_index(&self, dimension: u32) -> (u32, u64) {
+        let hash = stream_seed(self.seed, &[dimension as u64]);
+        (nested_uniform_scramble(self.index, hash as u32), stream_seed(hash, &[0]))
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_sample(&mut self, index: u32) {
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let dimension = self.dimension;
+        self.dimension += 1;
+
+        let (index, scramble) = self.shuffled_index(dimension);
+        u32_to_unit(nested_uniform_scramble(index.reverse_bits(), scramble as u32))
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let dimension = self.dimension;
+        self.dimension += 2;
+
+        let (index, scramble) = self.shuffled_index(dimension);
+        (
+            u32_to_unit(nested_uniform_scramble(index.reverse_bits(), scramble as u32)),
+            u32_to_unit(nested_uniform_scramble(
+                sobol_second_dimension(index),
+                (scramble >> 32) as u32,
+            )),
+        )
+    }
+}
+
+// Second Sobol dimension, whose direction numbers follow from the
+// primitive polynomial x + 1. The first is just the bits reversed.
+fn sobol_second_dimension(mut index: u32) -> u32 {
+    let mut result = 0;
+    let mut direction = 1u32 << 31;
+    while index != 0 {
+        if index & 1 == 1 {
+            result ^= direction;
+        }
+        index >>= 1;
+        direction ^= direction >> 1;
+    }
+    result
+}
+
+// Hash that only lets each bit depend on the bits below it (Laine and
+// Karras), so applied to reversed bits it acts as an Owen scramble
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50b47c);
+    x ^= x.wrapping_mul(0xb82f1e52);
+    x ^= x.wrapping_mul(0xc7afe638);
+    x ^= x.wrapping_mul(0x8d22f6e6);
+    x
+}
+
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sampler(kind: SamplerKind, pixel: u32, samples_per_pixel: u32) -> SamplerEnum {
+        kind.pixel_sampler(7, pixel, 3, samples_per_pixel, UniGen0_1::seeded(1).into_rng())
+    }
+
+    // Bin of each point, for `columns` by `rows` equal cells
+    fn cell((x, y): (f64, f64), columns: u32, rows: u32) -> usize {
+        ((y * rows as f64) as u32 * columns + (x * columns as f64) as u32) as usize
+    }
+
+    #[test]
+    fn permutation_element_is_a_bijection() {
+        for n in [1, 2, 3, 7, 8, 41, 64, 100, 311] {
+            for seed in [0, 1, 0x9e3779b9, 0xdeadbeef, u32::MAX] {
+                let mut seen = vec![false; n as usize];
+                for i in 0..n {
+                    let element = permutation_element(i, n, seed) as usize;
+                    assert!(!seen[element], "n {} seed {:#x} repeats {}", n, seed, element);
+                    seen[element] = true;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn sobol_pairs_fill_every_elementary_interval() {
+        for m in [2, 4, 6] {
+            let count = 1u32 << m;
+            for pixel in 0..4 {
+                let mut sampler = sampler(SamplerKind::Sobol, pixel, count);
+                let points: Vec<Vec<(f64, f64)>> = (0..count)
+                    .map(|index| {
+                        sampler.start_sample(index);
+                        (0..4).map(|_| sampler.get_2d()).collect()
+                    })
+                    .collect();
+                for dimension in 0..4 {
+                    // Every split of the unit square into 2^m cells of 2^a by 2^(m - a)
+                    for a in 0..=m {
+                        let (columns, rows) = (1 << a, 1 << (m - a));
+                        let mut hits = vec![0; count as usize];
+                        for point in &points {
+                            hits[cell(point[dimension], columns, rows)] += 1;
+                        }
+                        assert!(hits.iter().all(|&hit| hit == 1), "m {} a {}", m, a);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn stratified_puts_one_sample_in_each_stratum_per_round() {
+        for strata in [1u32, 5, 12, 16] {
+            // 2D rounds cover the whole grid, which may have a few more cells
+            let columns = (strata as f64).sqrt().ceil() as u32;
+            let rows = strata.div_ceil(columns);
+            let cells = columns * rows;
+            let mut sampler = sampler(SamplerKind::Stratified, 0, strata);
+            for round in 0..2 {
+                let mut hits_1d = vec![0; strata as usize];
+                for index in round * strata..(round + 1) * strata {
+                    sampler.start_sample(index);
+                    hits_1d[(sampler.get_1d() * strata as f64) as usize] += 1;
+                }
+                assert!(hits_1d.iter().all(|&hit| hit == 1), "strata {}", strata);
+
+                let mut hits_2d = vec![0; cells as usize];
+                for index in round * cells..(round + 1) * cells {
+                    sampler.start_sample(index);
+                    hits_2d[cell(sampler.get_2d(), columns, rows)] += 1;
+                }
+                assert!(hits_2d.iter().all(|&hit| hit == 1), "strata {}", strata);
+            }
+        }
+    }
+
+    #[test]
+    fn owen_scrambled_radical_inverse_is_stratified_and_in_range() {
+        for &base in &PRIMES {
+            for seed in [0, 1, 0xdeadbeef] {
+                let mut hits = vec![0; base as usize];
+                for index in 0..base {
+                    let value = owen_scrambled_radical_inverse(base, index, seed);
+                    assert!((0.0..1.0).contains(&value), "base {} value {}", base, value);
+                    hits[(value * base as f64) as usize] += 1;
+                }
+                assert!(hits.iter().all(|&hit| hit == 1), "base {}", base);
+                for index in [base, 1 << 20, u32::MAX] {
+                    let value = owen_scrambled_radical_inverse(base, index, seed);
+                    assert!((0.0..1.0).contains(&value), "base {} value {}", base, value);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn every_sampler_stays_in_range() {
+        for kind in [
+            SamplerKind::Independent,
+            SamplerKind::Stratified,
+            SamplerKind::Halton,
+            SamplerKind::Sobol,
+        ] {
+            let mut sampler = sampler(kind, 5, 16);
+            for index in 0..64 {
+                sampler.start_sample(index);
+                // Past the end of the Halton primes too
+                for _ in 0..40 {
+                    let value = sampler.get_1d();
+                    let (x, y) = sampler.get_2d();
+                    for value in [value, x, y] {
+                        assert!((0.0..1.0).contains(&value), "{:?} {}", kind, value);
+                    }
+                }
+            }
+        }
+    }
+}
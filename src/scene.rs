@@ -0,0 +1,1030 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::aabb::Aabb;
+use crate::background::Background;
+use crate::camera::{
+    CameraEnum, EquirectangularCamera, FisheyeCamera, FisheyeMapping, OrthographicCamera,
+    PerspectiveCamera,
+};
+use crate::bvh::BvhNode;
+use crate::environment::EnvironmentMap;
+use crate::framebuffer::Framebuffer;
+use crate::grid::{has_volume, VoxelGrid};
+use crate::hit::{Hittable, HittableList};
+use crate::material::*;
+use crate::instance::Instance;
+use crate::medium::{ConstantMedium, GridMedium};
+use crate::moving_sphere::MovingSphere;
+use crate::obj::{ObjError, ObjModel};
+use crate::perlin::Perlin;
+use crate::filter::Filter;
+use crate::sampler::SamplerKind;
+use crate::settings::{AdaptiveSettings, RenderSettings};
+use crate::sphere::Sphere;
+use crate::texture::*;
+use crate::tiles::TileOrder;
+use crate::tonemap::{ToneMapOperator, ToneMapSettings};
+use crate::transform::{Mat4, Transform};
+use crate::triangle::Triangle;
+use crate::vec3::*;
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    // `field` is the location inside the document, e.g. "objects[3].radius"
+    Schema {
+        field: String,
+        message: String,
+    },
+    Mesh {
+        field: String,
+        source: ObjError,
+    },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            SceneError::Schema { field, message } => write!(f, "{}: {}", field, message),
+            SceneError::Mesh { field, source } => write!(f, "{}: {}", field, source),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SceneError::Io { source, .. } => Some(source),
+            SceneError::Schema { .. } => None,
+            SceneError::Mesh { source, .. } => Some(source),
+        }
+    }
+}
+
+fn schema_error(field: impl Into<String>, message: impl Into<String>) -> SceneError {
+    SceneError::Schema {
+        field: field.into(),
+        message: message.into(),
+    }
+}
+
+fn default_v_up() -> Vec3 {
+    Vec3 {
+        x: 0.0,
+        y: 1.0,
+        z: 0.0,
+    }
+}
+
+// How the camera maps directions onto the image
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ProjectionDescription {
+    #[default]
+    Perspective,
+    // `height` of the view in world units, by default the height the
+    // perspective view has at look_at
+    Orthographic {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        height: Option<f64>,
+    },
+    // Circular fisheye spanning v_fov degrees across its image circle
+    Fisheye {
+        #[serde(default)]
+        mapping: FisheyeMapping,
+    },
+    // 360 degree panorama
+    Equirectangular,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CameraDescription {
+    #[serde(default)]
+    pub projection: ProjectionDescription,
+    pub look_from: Vec3,
+    pub look_at: Vec3,
+    #[serde(default = "default_v_up")]
+    pub v_up: Vec3,
+    pub v_fov: f64,
+    // Depth of field, for the perspective projection only
+    #[serde(default)]
+    pub aperture: f64,
+    // Defaults to the distance between look_from and look_at
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub focus_dist: Option<f64>,
+    // Rays are spread over [shutter_open, shutter_close] for motion blur
+    #[serde(default)]
+    pub shutter_open: f64,
+    #[serde(default)]
+    pub shutter_close: f64,
+}
+
+impl CameraDescription {
+    pub fn build(&self, aspect_ratio: f64) -> Result<CameraEnum, SceneError> {
+        let look_distance = (self.look_from - self.look_at).length();
+        if self.shutter_open.is_nan() || self.shutter_close.is_nan() {
+            return Err(schema_error("camera.shutter_open", "must be a number"));
+        }
+        if self.shutter_close < self.shutter_open {
+            return Err(schema_error(
+                "camera.shutter_close",
+                "must not be before shutter_open",
+            ));
+        }
+        // Perspective cameras, and orthographic ones sized by it, need a
+        // field of view a pinhole can see
+        let uses_perspective_fov = match self.projection {
+            ProjectionDescription::Perspective => true,
+            ProjectionDescription::Orthographic { height } => height.is_none(),
+            _ => false,
+        };
+        let fov_in_range = self.v_fov > 0.0 && self.v_fov < 180.0;
+        if uses_perspective_fov && !fov_in_range {
+            return Err(schema_error("camera.v_fov", "must be between 0 and 180"));
+        }
+        Ok(match self.projection {
+            ProjectionDescription::Perspective => PerspectiveCamera::new(
+                self.look_from,
+                self.look_at,
+                self.v_up,
+                self.v_fov,
+                aspect_ratio,
+                self.aperture,
+                self.focus_dist.unwrap_or(look_distance),
+                self.shutter_open,
+                self.shutter_close,
+            )
+            .into(),
+            ProjectionDescription::Orthographic { height } => {
+                let height = height
+                    .unwrap_or_else(|| 2.0 * look_distance * (self.v_fov.to_radians() / 2.0).tan());
+                if height.is_nan() || height <= 0.0 {
+                    return Err(schema_error("camera.projection.height", "must be positive"));
+                }
+                OrthographicCamera::new(
+                    self.look_from,
+                    self.look_at,
+                    self.v_up,
+                    height,
+                    aspect_ratio,
+                    self.shutter_open,
+                    self.shutter_close,
+                )
+                .into()
+            }
+            ProjectionDescription::Fisheye { mapping } => {
+                if self.v_fov.is_nan() || self.v_fov <= 0.0 || self.v_fov > 360.0 {
+                    return Err(schema_error(
+                        "camera.v_fov",
+                        "must be between 0 and 360 for a fisheye",
+                    ));
+                }
+                FisheyeCamera::new(
+                    self.look_from,
+                    self.look_at,
+                    self.v_up,
+                    self.v_fov,
+                    mapping,
+                    aspect_ratio,
+                    self.shutter_open,
+                    self.shutter_close,
+                )
+                .into()
+            }
+            ProjectionDescription::Equirectangular => EquirectangularCamera::new(
+                self.look_from,
+                self.look_at,
+                self.v_up,
+                self.shutter_open,
+                self.shutter_close,
+            )
+            .into(),
+        })
+    }
+}
+
+fn default_width() -> u32 {
+    500
+}
+
+fn default_samples_per_pixel() -> u32 {
+    50
+}
+
+fn default_max_depth() -> u32 {
+    50
+}
+
+fn default_roulette_depth() -> u32 {
+    3
+}
+
+fn default_tile_size() -> u32 {
+    32
+}
+
+// Height may be given directly or derived from an aspect ratio (16:9 if neither)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ImageDescription {
+    #[serde(default = "default_width")]
+    pub width: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aspect_ratio: Option<f64>,
+    #[serde(default = "default_samples_per_pixel")]
+    pub samples_per_pixel: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub samples_per_pass: Option<u32>,
+    #[serde(default)]
+    pub sampler: SamplerKind,
+    #[serde(default)]
+    pub filter: Filter,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub adaptive: Option<AdaptiveSettings>,
+    #[serde(default = "default_max_depth")]
+    pub max_depth: u32,
+    #[serde(default = "default_roulette_depth")]
+    pub roulette_depth: u32,
+    #[serde(default)]
+    pub seed: u64,
+    #[serde(default)]
+    pub tone_map: ToneMapSettings,
+    #[serde(default = "default_tile_size")]
+    pub tile_size: u32,
+    #[serde(default)]
+    pub tile_order: TileOrder,
+}
+
+impl Default for ImageDescription {
+    fn default() -> Self {
+        ImageDescription {
+            width: default_width(),
+            height: None,
+            aspect_ratio: None,
+            samples_per_pixel: default_samples_per_pixel(),
+            samples_per_pass: None,
+            sampler: SamplerKind::default(),
+            filter: Filter::default(),
+            adaptive: None,
+            max_depth: default_max_depth(),
+            roulette_depth: default_roulette_depth(),
+            seed: 0,
+            tone_map: ToneMapSettings::default(),
+            tile_size: default_tile_size(),
+            tile_order: TileOrder::default(),
+        }
+    }
+}
+
+impl ImageDescription {
+    pub fn to_settings(&self) -> Result<RenderSettings, SceneError> {
+        if self.width == 0 {
+            return Err(schema_error("image.width", "must be greater than 0"));
+        }
+        if self.samples_per_pixel == 0 {
+            return Err(schema_error("image.samples_per_pixel", "must be greater than 0"));
+        }
+        if self.samples_per_pass == Some(0) {
+            return Err(schema_error("image.samples_per_pass", "must be greater than 0"));
+        }
+        if self.filter.radius() <= 0.0 || !self.filter.radius().is_finite() {
+            return Err(schema_error("image.filter.radius", "must be positive"));
+        }
+        if let Filter::Gaussian { sigma, .. } = self.filter {
+            if sigma.is_nan() || sigma <= 0.0 {
+                return Err(schema_error("image.filter.sigma", "must be positive"));
+            }
+        }
+        if let Some(adaptive) = &self.adaptive {
+            if adaptive.threshold.is_nan() || adaptive.threshold <= 0.0 {
+                return Err(schema_error("image.adaptive.threshold", "must be positive"));
+            }
+            if adaptive.max_samples_per_pixel < self.samples_per_pixel {
+                return Err(schema_error(
+                    "image.adaptive.max_samples_per_pixel",
+                    "must be at least samples_per_pixel",
+                ));
+            }
+        }
+        if self.tile_size == 0 {
+            return Err(schema_error("image.tile_size", "must be greater than 0"));
+        }
+        if let ToneMapOperator::ExtendedReinhard { white_point } = self.tone_map.operator {
+            if white_point.is_nan() || white_point <= 0.0 {
+                return Err(schema_error(
+                    "image.tone_map.operator.white_point",
+                    "must be positive",
+                ));
+            }
+        }
+        let image_height = match (self.height, self.aspect_ratio) {
+            (Some(0), _) => return Err(schema_error("image.height", "must be greater than 0")),
+            (Some(height), _) => height,
+            (None, Some(aspect_ratio)) if aspect_ratio <= 0.0 => {
+                return Err(schema_error("image.aspect_ratio", "must be positive"))
+            }
+            (None, aspect_ratio) => {
+                let aspect_ratio = aspect_ratio.unwrap_or(16.0 / 9.0);
+                ((self.width as f64 / aspect_ratio).ceil() as u32).max(1)
+            }
+        };
+        Ok(RenderSettings {
+            image_width: self.width,
+            image_height,
+            samples_per_pixel: self.samples_per_pixel,
+            samples_per_pass: self.samples_per_pass,
+            sampler: self.sampler,
+            filter: self.filter,
+            adaptive: self.adaptive,
+            max_depth: self.max_depth,
+            roulette_depth: self.roulette_depth,
+            seed: self.seed,
+            tone_map: self.tone_map,
+            tile_size: self.tile_size,
+            tile_order: self.tile_order,
+        })
+    }
+}
+
+fn default_intensity() -> f64 {
+    1.0
+}
+
+// Either "sky", {"solid": [r, g, b]} or {"environment": {"path": ...}}
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackgroundDescription {
+    #[default]
+    Sky,
+    Solid(Vec3),
+    // Equirectangular .hdr or .exr image relative to the scene file,
+    // rotated `rotation` degrees about the up (+y) axis
+    Environment {
+        path: PathBuf,
+        #[serde(default)]
+        rotation: f64,
+        #[serde(default = "default_intensity")]
+        intensity: f64,
+    },
+}
+
+impl BackgroundDescription {
+    pub fn build(&self, base_dir: &Path) -> Result<Background, SceneError> {
+        match self {
+            BackgroundDescription::Sky => Ok(Background::Sky),
+            BackgroundDescription::Solid(color) => Ok(Background::Solid(*color)),
+            BackgroundDescription::Environment {
+                path,
+                rotation,
+                intensity,
+            } => {
+                let path = base_dir.join(path);
+                let map = EnvironmentMap::load(&path, *rotation, *intensity)
+                    .map_err(|source| SceneError::Io { path, source })?;
+                Ok(Background::Environment(Arc::new(map)))
+            }
+        }
+    }
+}
+
+fn default_time1() -> f64 {
+    1.0
+}
+
+fn default_density_scale() -> f64 {
+    1.0
+}
+
+fn default_checker_scale() -> f64 {
+    1.0
+}
+
+fn default_noise_scale() -> f64 {
+    1.0
+}
+
+// Either a plain [r, g, b] color or an object with a "type"
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum TextureDescription {
+    // Cubes of side `scale` in world space
+    Checker {
+        #[serde(default = "default_checker_scale")]
+        scale: f64,
+        even: Box<TextureDescription>,
+        odd: Box<TextureDescription>,
+    },
+    // Relative to the scene file; 8-bit images are assumed to be sRGB
+    Image { path: PathBuf },
+    Noise {
+        #[serde(default = "default_noise_scale")]
+        scale: f64,
+        #[serde(default)]
+        style: NoiseStyle,
+        #[serde(default = "Vec3::ones")]
+        color: Vec3,
+        #[serde(default)]
+        seed: u64,
+    },
+    #[serde(untagged)]
+    Color(Vec3),
+}
+
+impl From<Vec3> for TextureDescription {
+    fn from(color: Vec3) -> TextureDescription {
+        TextureDescription::Color(color)
+    }
+}
+
+impl TextureDescription {
+    pub fn build(&self, base_dir: &Path) -> Result<TextureEnum, SceneError> {
+        Ok(match self {
+            TextureDescription::Color(color) => (*color).into(),
+            TextureDescription::Checker { scale, even, odd } => Checker {
+                scale: *scale,
+                even: Arc::new(even.build(base_dir)?),
+                odd: Arc::new(odd.build(base_dir)?),
+            }
+            .into(),
+            TextureDescription::Image { path } => {
+                let path = base_dir.join(path);
+                let image = Framebuffer::load(&path)
+                    .map_err(|source| SceneError::Io { path, source })?;
+                ImageTexture {
+                    image: Arc::new(image),
+                }
+                .into()
+            }
+            TextureDescription::Noise {
+                scale,
+                style,
+                color,
+                seed,
+            } => NoiseTexture {
+                perlin: Arc::new(Perlin::new(*seed)),
+                scale: *scale,
+                style: *style,
+                color: *color,
+            }
+            .into(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum MaterialDescription {
+    Lambertian { albedo: TextureDescription },
+    Metal { albedo: TextureDescription, fuzz: f64 },
+    Dielectric { ir: f64 },
+    DiffuseLight { emit: Vec3 },
+}
+
+impl MaterialDescription {
+    // Textures with relative paths are resolved against `base_dir`
+    pub fn to_material(&self, base_dir: &Path) -> Result<MaterialEnum, SceneError> {
+        Ok(match self {
+            MaterialDescription::Lambertian { albedo } => Lambertian {
+                albedo: albedo.build(base_dir)?,
+            }
+            .into(),
+            MaterialDescription::Metal { albedo, fuzz } => Metal {
+                albedo: albedo.build(base_dir)?,
+                fuzz: *fuzz,
+            }
+            .into(),
+            MaterialDescription::Dielectric { ir } => Dielectric { ir: *ir }.into(),
+            MaterialDescription::DiffuseLight { emit } => DiffuseLight { emit: *emit }.into(),
+        })
+    }
+}
+
+// One step of an instance's placement. Steps apply in the order listed,
+// e.g. [{"scale": [2, 2, 2]}, {"rotate": {"axis": [0, 1, 0], "degrees": 45}}]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum TransformDescription {
+    Translate(Vec3),
+    Scale(Vec3),
+    Rotate { axis: Vec3, degrees: f64 },
+    // Row-major, acting on column vectors
+    Matrix([[f64; 4]; 4]),
+}
+
+impl TransformDescription {
+    pub fn to_matrix(&self) -> Mat4 {
+        match *self {
+            TransformDescription::Translate(offset) => Mat4::translation(offset),
+            TransformDescription::Scale(factors) => Mat4::scaling(factors),
+            TransformDescription::Rotate { axis, degrees } => Mat4::rotation(axis, degrees),
+            TransformDescription::Matrix(m) => Mat4 { m },
+        }
+    }
+
+    // None if the combined matrix is singular
+    pub fn build_all(steps: &[TransformDescription]) -> Option<Transform> {
+        let matrix = steps
+            .iter()
+            .fold(Mat4::identity(), |acc, step| step.to_matrix() * acc);
+        Transform::new(matrix)
+    }
+}
+
+// Objects refer to materials by their key in SceneDescription::materials
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ObjectDescription {
+    Sphere {
+        center: Vec3,
+        radius: f64,
+        material: String,
+    },
+    // Moves from center0 at time0 to center1 at time1
+    MovingSphere {
+        center0: Vec3,
+        center1: Vec3,
+        #[serde(default)]
+        time0: f64,
+        #[serde(default = "default_time1")]
+        time1: f64,
+        radius: f64,
+        material: String,
+    },
+    Triangle {
+        vertices: [Vec3; 3],
+        material: String,
+    },
+    // Fog or smoke filling a closed, convex boundary object. The boundary's
+    // material is ignored; light scatters off `albedo` inside the volume.
+    ConstantMedium {
+        boundary: Box<ObjectDescription>,
+        density: f64,
+        albedo: TextureDescription,
+    },
+    // Density from a voxel grid file, scaled by `density`. `.vol` files give
+    // their own resolution and bounds; raw f32 files need `resolution` and
+    // fill the unit cube unless `bounds` is set. `g` shapes the phase
+    // function, from backward (-1) through isotropic (0) to forward (1).
+    GridMedium {
+        path: PathBuf,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        resolution: Option<[usize; 3]>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        bounds: Option<[Vec3; 2]>,
+        #[serde(default = "default_density_scale")]
+        density: f64,
+        albedo: TextureDescription,
+        #[serde(default)]
+        g: f64,
+    },
+    // A prototype from SceneDescription::prototypes placed by `transform`
+    Instance {
+        prototype: String,
+        #[serde(default)]
+        transform: Vec<TransformDescription>,
+    },
+    // Wavefront OBJ file, relative to the scene file; materials come from its MTL.
+    // If `group` is set only the faces of that group are loaded.
+    Mesh {
+        path: PathBuf,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        group: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneDescription {
+    pub camera: CameraDescription,
+    #[serde(default)]
+    pub image: ImageDescription,
+    #[serde(default)]
+    pub background: BackgroundDescription,
+    #[serde(default)]
+    pub materials: BTreeMap<String, MaterialDescription>,
+    #[serde(default)]
+    pub objects: Vec<ObjectDescription>,
+    // Named groups of objects that "instance" objects place in the scene
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub prototypes: BTreeMap<String, Vec<ObjectDescription>>,
+}
+
+// Everything needed to render, built from a SceneDescription.
+// The world is already wrapped in a BvhNode.
+pub struct Scene {
+    pub camera: CameraEnum,
+    pub world: Hittable,
+    pub background: Background,
+    pub settings: RenderSettings,
+}
+
+impl SceneDescription {
+    pub fn load(path: impl AsRef<Path>) -> Result<SceneDescription, SceneError> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|source| SceneError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let deserializer = &mut serde_json::Deserializer::from_reader(BufReader::new(file));
+        serde_path_to_error::deserialize(deserializer)
+            .map_err(|err| schema_error(err.path().to_string(), err.into_inner().to_string()))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SceneError> {
+        let path = path.as_ref();
+        let io_error = |source| SceneError::Io {
+            path: path.to_path_buf(),
+            source,
+        };
+        let file = File::create(path).map_err(io_error)?;
+        serde_json::to_writer_pretty(BufWriter::new(file), self)
+            .map_err(|err| io_error(err.into()))
+    }
+
+    // Fingerprint of everything that changes which samples a render takes
+    // or how they are added up, for checking a checkpoint belongs to this
+    // scene. Settings that only change how many samples are taken, how the
+    // work is scheduled or how the result is displayed are left out, so a
+    // render can be resumed with more samples. FNV-1a over the JSON form,
+    // which is stable across runs.
+    pub fn checkpoint_hash(&self) -> u64 {
+        let mut description = self.clone();
+        let image = &mut description.image;
+        // Except for the stratified sampler, whose strata are laid out over
+        // samples_per_pixel
+        if image.sampler != SamplerKind::Stratified {
+            image.samples_per_pixel = 0;
+        }
+        image.samples_per_pass = None;
+        image.adaptive = None;
+        image.tile_size = 0;
+        image.tile_order = TileOrder::default();
+        image.tone_map = ToneMapSettings::default();
+
+        let json = serde_json::to_vec(&description).expect("scene descriptions always serialize");
+        json.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        })
+    }
+
+    // Relative mesh and image paths are resolved against `base_dir`
+    pub fn build(&self, base_dir: &Path) -> Result<Scene, SceneError> {
+        let settings = self.image.to_settings()?;
+        let camera = self.camera.build(settings.aspect_ratio())?;
+
+        // Built once so image textures are loaded once, however many objects use them
+        let mut builder = WorldBuilder {
+            base_dir,
+            materials: BTreeMap::new(),
+            prototypes: BTreeMap::new(),
+        };
+        for (name, description) in &self.materials {
+            if let MaterialDescription::Dielectric { ir } = description {
+                if ir.is_nan() || *ir <= 0.0 {
+                    return Err(schema_error(format!("materials.{}.ir", name), "must be positive"));
+                }
+            }
+            builder
+                .materials
+                .insert(name.as_str(), description.to_material(base_dir)?);
+        }
+
+        // Each prototype gets its own hierarchy, shared by all its instances
+        let mut prototypes = BTreeMap::new();
+        for (name, objects) in &self.prototypes {
+            let mut list = HittableList::new();
+            for (index, object) in objects.iter().enumerate() {
+                let field = format!("prototypes.{}[{}]", name, index);
+                match object {
+                    ObjectDescription::Instance { .. } => {
+                        return Err(schema_error(field, "prototypes can't contain instances"));
+                    }
+                    // Media are only found at the top level of the world
+                    ObjectDescription::ConstantMedium { .. }
+                    | ObjectDescription::GridMedium { .. } => {
+                        return Err(schema_error(field, "prototypes can't contain media"));
+                    }
+                    _ => {}
+                }
+                builder.add_object(object, field, &mut list)?;
+            }
+            if list.is_empty() {
+                return Err(schema_error(format!("prototypes.{}", name), "has no objects"));
+            }
+            prototypes.insert(name.as_str(), Arc::new(BvhNode::new(list).into()));
+        }
+        builder.prototypes = prototypes;
+
+        let mut world = HittableList::new();
+        for (index, object) in self.objects.iter().enumerate() {
+            builder.add_object(object, format!("objects[{}]", index), &mut world)?;
+        }
+
+        Ok(Scene {
+            camera,
+            world: BvhNode::new(world).into(),
+            background: self.background.build(base_dir)?,
+            settings,
+        })
+    }
+}
+
+// Materials and prototypes already built, shared by every object that uses them
+struct WorldBuilder<'a> {
+    base_dir: &'a Path,
+    materials: BTreeMap<&'a str, MaterialEnum>,
+    prototypes: BTreeMap<&'a str, Arc<Hittable>>,
+}
+
+impl WorldBuilder<'_> {
+    fn material(&self, name: &str, field: String) -> Result<MaterialEnum, SceneError> {
+        self.materials
+            .get(name)
+            .cloned()
+            .ok_or_else(|| schema_error(field, format!("unknown material '{}'", name)))
+    }
+
+    // Objects that expand to several hittables, like meshes, are put in a BvhNode
+    fn single_object(&self, object: &ObjectDescription, field: String) -> Result<Hittable, SceneError> {
+        let mut list = HittableList::new();
+        self.add_object(object, field, &mut list)?;
+        if list.len() == 1 {
+            Ok(list.pop().unwrap())
+        } else {
+            Ok(BvhNode::new(list).into())
+        }
+    }
+
+    fn add_object(
+        &self,
+        object: &ObjectDescription,
+        field: String,
+        world: &mut HittableList,
+    ) -> Result<(), SceneError> {
+        match object {
+            ObjectDescription::Sphere { radius, .. } | ObjectDescription::MovingSphere { radius, .. }
+                if !radius.is_finite() || *radius == 0.0 =>
+            {
+                // Negative radii are allowed, they turn the normals inwards
+                return Err(schema_error(field + ".radius", "must be finite and nonzero"));
+            }
+            ObjectDescription::Sphere {
+                center,
+                radius,
+                material: name,
+            } => world.push(
+                Sphere {
+                    center: *center,
+                    radius: *radius,
+                    material: self.material(name, field + ".material")?,
+                }
+                .into(),
+            ),
+            ObjectDescription::MovingSphere {
+                center0,
+                center1,
+                time0,
+                time1,
+                radius,
+                material: name,
+            } => world.push(
+                MovingSphere {
+                    center0: *center0,
+                    center1: *center1,
+                    time0: *time0,
+                    time1: *time1,
+                    radius: *radius,
+                    material: self.material(name, field + ".material")?,
+                }
+                .into(),
+            ),
+            ObjectDescription::Triangle {
+                vertices,
+                material: name,
+            } => world.push(
+                Triangle {
+                    vertices: *vertices,
+                    material: self.material(name, field + ".material")?,
+                }
+                .into(),
+            ),
+            ObjectDescription::Mesh { path, group } => {
+                let model = ObjModel::load(self.base_dir.join(path)).map_err(|source| {
+                    SceneError::Mesh {
+                        field: field.clone() + ".path",
+                        source,
+                    }
+                })?;
+                match group {
+                    Some(name) => {
+                        let faces = model.group_hittables(name);
+                        if faces.is_empty() {
+                            return Err(schema_error(
+                                field + ".group",
+                                format!("no faces in group '{}'", name),
+                            ));
+                        }
+                        world.extend(faces);
+                    }
+                    None => world.extend(model.hittables()),
+                }
+            }
+            ObjectDescription::ConstantMedium {
+                boundary,
+                density,
+                albedo,
+            } => {
+                if density.is_nan() || *density <= 0.0 {
+                    return Err(schema_error(field + ".density", "must be positive"));
+                }
+                let boundary = self.single_object(boundary, field + ".boundary")?;
+                let albedo = albedo.build(self.base_dir)?;
+                world.push(ConstantMedium::new(boundary, *density, albedo).into());
+            }
+            ObjectDescription::GridMedium {
+                path,
+                resolution,
+                bounds,
+                density,
+                albedo,
+                g,
+            } => {
+                if density.is_nan() || *density < 0.0 {
+                    return Err(schema_error(field + ".density", "must not be negative"));
+                }
+                if !(-1.0 < *g && *g < 1.0) {
+                    return Err(schema_error(field + ".g", "must be between -1 and 1"));
+                }
+                let path = self.base_dir.join(path);
+                let mut grid = VoxelGrid::load(&path, *resolution)
+                    .map_err(|source| SceneError::Io { path, source })?;
+                if let Some([minimum, maximum]) = bounds {
+                    grid.bounds = Aabb::new(*minimum, *maximum);
+                }
+                if !has_volume(&grid.bounds) {
+                    return Err(schema_error(field + ".bounds", "box is empty"));
+                }
+                let phase_function = HenyeyGreenstein {
+                    albedo: albedo.build(self.base_dir)?,
+                    g: *g,
+                };
+                world.push(GridMedium::new(grid, *density, phase_function.into()).into());
+            }
+            ObjectDescription::Instance {
+                prototype,
+                transform,
+            } => {
+                let object = self.prototypes.get(prototype.as_str()).ok_or_else(|| {
+                    schema_error(
+                        field.clone() + ".prototype",
+                        format!("unknown prototype '{}'", prototype),
+                    )
+                })?;
+                let transform = TransformDescription::build_all(transform)
+                    .ok_or_else(|| schema_error(field + ".transform", "is not invertible"))?;
+                world.push(Instance::new(Arc::clone(object), transform).into());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::presets;
+
+    fn build_with(edit: impl FnOnce(&mut SceneDescription)) -> Result<Scene, SceneError> {
+        let mut description = presets::cornell_box();
+        edit(&mut description);
+        description.build(Path::new(""))
+    }
+
+    fn schema_field(result: Result<Scene, SceneError>) -> String {
+        match result {
+            Err(SceneError::Schema { field, .. }) => field,
+            Err(err) => panic!("expected a schema error, got {}", err),
+            Ok(_) => panic!("expected a schema error, but the scene built"),
+        }
+    }
+
+    fn glass_sphere(radius: f64) -> impl FnOnce(&mut SceneDescription) {
+        move |description| {
+            description
+                .materials
+                .insert("glass".to_string(), MaterialDescription::Dielectric { ir: 1.5 });
+            description.objects.push(ObjectDescription::Sphere {
+                center: Vec3::zeros(),
+                radius,
+                material: "glass".to_string(),
+            });
+        }
+    }
+
+    #[test]
+    fn rejects_degenerate_cameras() {
+        for v_fov in [0.0, 180.0, 200.0, f64::NAN] {
+            let result = build_with(|description| description.camera.v_fov = v_fov);
+            assert_eq!(schema_field(result), "camera.v_fov", "v_fov {}", v_fov);
+        }
+        let result = build_with(|description| {
+            description.camera.shutter_open = 1.0;
+            description.camera.shutter_close = 0.5;
+        });
+        assert_eq!(schema_field(result), "camera.shutter_close");
+    }
+
+    #[test]
+    fn rejects_degenerate_spheres_and_glass() {
+        for radius in [0.0, f64::INFINITY, f64::NAN] {
+            let field = schema_field(build_with(glass_sphere(radius)));
+            assert!(field.ends_with(".radius"), "radius {}", radius);
+        }
+        // Negative radii make hollow spheres
+        build_with(glass_sphere(-1.0)).unwrap();
+
+        let result = build_with(|description| {
+            description
+                .materials
+                .insert("glass".to_string(), MaterialDescription::Dielectric { ir: 0.0 });
+        });
+        assert_eq!(schema_field(result), "materials.glass.ir");
+    }
+
+    #[test]
+    fn rejects_nan_settings() {
+        let result = build_with(|description| {
+            description.image.adaptive = Some(AdaptiveSettings {
+                threshold: f64::NAN,
+                ..Default::default()
+            });
+        });
+        assert_eq!(schema_field(result), "image.adaptive.threshold");
+
+        let result = build_with(|description| {
+            description.image.filter = Filter::Gaussian {
+                radius: 1.5,
+                sigma: f64::NAN,
+            };
+        });
+        assert_eq!(schema_field(result), "image.filter.sigma");
+
+        let result = build_with(|description| {
+            description.objects.push(ObjectDescription::ConstantMedium {
+                boundary: Box::new(ObjectDescription::Sphere {
+                    center: Vec3::zeros(),
+                    radius: 1.0,
+                    material: "white".to_string(),
+                }),
+                density: f64::NAN,
+                albedo: Vec3::ones().into(),
+            });
+        });
+        assert!(schema_field(result).ends_with(".density"));
+    }
+
+    #[test]
+    fn rejects_media_in_prototypes() {
+        let result = build_with(|description| {
+            let fog = ObjectDescription::ConstantMedium {
+                boundary: Box::new(ObjectDescription::Sphere {
+                    center: Vec3::zeros(),
+                    radius: 1.0,
+                    material: "white".to_string(),
+                }),
+                density: 0.5,
+                albedo: Vec3::ones().into(),
+            };
+            description.prototypes.insert("fog".to_string(), vec![fog]);
+        });
+        assert_eq!(schema_field(result), "prototypes.fog[0]");
+
+        // Rejected before the grid is loaded
+        let result = build_with(|description| {
+            let smoke = ObjectDescription::GridMedium {
+                path: PathBuf::from("smoke.vol"),
+                resolution: None,
+                bounds: None,
+                density: 1.0,
+                albedo: Vec3::ones().into(),
+                g: 0.0,
+            };
+            description.prototypes.insert("smoke".to_string(), vec![smoke]);
+        });
+        assert_eq!(schema_field(result), "prototypes.smoke[0]");
+    }
+}
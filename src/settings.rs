@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+
+use crate::filter::Filter;
+use crate::sampler::SamplerKind;
+use crate::tiles::TileOrder;
+use crate::tonemap::ToneMapSettings;
+
+// Image and sampling parameters for one render
+#[derive(Debug, Clone)]
+pub struct RenderSettings {
+    pub image_width: u32,
+    pub image_height: u32,
+    pub samples_per_pixel: u32,
+    // Samples added to every pixel per progressive pass; None renders
+    // everything in one pass
+    pub samples_per_pass: Option<u32>,
+    // How the random numbers for each sample are chosen
+    pub sampler: SamplerKind,
+    // How samples are weighted into the pixels around them
+    pub filter: Filter,
+    // Keeps sampling noisy pixels once all have samples_per_pixel
+    pub adaptive: Option<AdaptiveSettings>,
+    pub max_depth: u32,
+    // Bounces after which Russian roulette may end a path
+    pub roulette_depth: u32,
+    // Drives every random stream; equal seeds give bit-identical images
+    pub seed: u64,
+    // Used when converting the framebuffer to an 8-bit image
+    pub tone_map: ToneMapSettings,
+    // Edge length in pixels of the square tiles handed to render threads
+    pub tile_size: u32,
+    pub tile_order: TileOrder,
+}
+
+impl RenderSettings {
+    pub fn aspect_ratio(&self) -> f64 {
+        self.image_width as f64 / self.image_height as f64
+    }
+}
+
+fn default_adaptive_threshold() -> f64 {
+    0.01
+}
+
+fn default_max_samples_per_pixel() -> u32 {
+    1024
+}
+
+// Pixels keep getting samples_per_pass more samples while the standard
+// error of their mean, relative to the mean, is above `threshold`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AdaptiveSettings {
+    #[serde(default = "default_adaptive_threshold")]
+    pub threshold: f64,
+    #[serde(default = "default_max_samples_per_pixel")]
+    pub max_samples_per_pixel: u32,
+}
+
+impl Default for AdaptiveSettings {
+    fn default() -> Self {
+        AdaptiveSettings {
+            threshold: default_adaptive_threshold(),
+            max_samples_per_pixel: default_max_samples_per_pixel(),
+        }
+    }
+}
@@ -1,7 +1,12 @@
 use crate::vec3::*;
 use crate::ray::*;
 use crate::hit::*;
-use crate::material::MaterialEnum;
+use crate::material::{Material, MaterialEnum};
+use crate::aabb::Aabb;
+use crate::onb::Onb;
+use crate::sampler::{Sampler, SamplerEnum};
+
+use std::f64::consts::PI;
 
 pub struct Sphere {
     pub center: Vec3,
@@ -9,32 +14,116 @@ pub struct Sphere {
     pub material: MaterialEnum
 }
 
-impl Hit for Sphere {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let origin_to_center = r.origin - self.center;
-        let a = r.direction.length_squared();
-        let half_b = origin_to_center.dot(r.direction);
-        let c = origin_to_center.length_squared() - self.radius*self.radius;
-
-        let discriminant = half_b*half_b - a*c;
-        if discriminant < 0.0 {
+// Longitude and latitude of a point on the unit sphere, both in [0, 1].
+// u starts at -x and goes around through +z, v runs from -y up to +y.
+fn sphere_uv(p: Vec3) -> [f64; 2] {
+    let theta = (-p.y).clamp(-1.0, 1.0).acos();
+    let phi = (-p.z).atan2(p.x) + PI;
+    [phi / (2.0 * PI), theta / PI]
+}
+
+// Shared by Sphere and MovingSphere, with the center at the ray's time
+pub fn hit_sphere<'a>(
+    center: Vec3,
+    radius: f64,
+    material: &'a MaterialEnum,
+    r: &Ray,
+    t_min: f64,
+    t_max: f64,
+) -> Option<HitRecord<'a>> {
+    let origin_to_center = r.origin - center;
+    let a = r.direction.length_squared();
+    let half_b = origin_to_center.dot(r.direction);
+    let c = origin_to_center.length_squared() - radius * radius;
+
+    let discriminant = half_b * half_b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    let sqrtd = discriminant.sqrt();
+    let mut root = (-half_b - sqrtd) / a;
+    if root < t_min || root > t_max {
+        root = (-half_b + sqrtd) / a;
+        if root < t_min || root > t_max {
             return None;
         }
+    }
 
-        let sqrtd = discriminant.sqrt();
-        let mut root = (-half_b - sqrtd) / a;
-        if root < t_min || root > t_max {
-            root = (-half_b + sqrtd) / a;
-            if root < t_min || root > t_max {
-                return None;
-            }
+    let record_p = r.at(root);
+    let outward_normal = (record_p - center) / radius;
+
+    let mut hit_record = HitRecord::new(record_p, root, material, outward_normal, r);
+    [hit_record.u, hit_record.v] = sphere_uv(outward_normal);
+
+    Some(hit_record)
+}
+
+pub fn sphere_bounding_box(center: Vec3, radius: f64) -> Aabb {
+    let radius = radius.abs();
+    let radius_vec = Vec3 {
+        x: radius,
+        y: radius,
+        z: radius,
+    };
+    Aabb::new(center - radius_vec, center + radius_vec)
+}
+
+impl Hit for Sphere {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        hit_sphere(self.center, self.radius, &self.material, r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(sphere_bounding_box(self.center, self.radius))
+    }
+
+    fn is_light(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    // Directions are sampled uniformly inside the cone the sphere subtends.
+    // From inside the sphere every direction is equally likely.
+    fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f64 {
+        let r = Ray {
+            origin,
+            direction,
+            time: 0.0,
+        };
+        if self.hit(&r, 0.001, f64::INFINITY).is_none() {
+            return 0.0;
+        }
+
+        let distance_squared = (self.center - origin).length_squared();
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            return 1.0 / (4.0 * PI);
         }
 
-        let record_p = r.at(root);
-        let outward_normal = (record_p - self.center) / self.radius;
+        let cos_theta_max = (1.0 - radius_squared / distance_squared).sqrt();
+        let solid_angle = 2.0 * PI * (1.0 - cos_theta_max);
+        1.0 / solid_angle
+    }
+
+    fn random_direction(&self, origin: Vec3, sampler: &mut SamplerEnum) -> Vec3 {
+        let direction = self.center - origin;
+        let distance_squared = direction.length_squared();
+        let radius_squared = self.radius * self.radius;
+
+        let (r1, r2) = sampler.get_2d();
+        let phi = 2.0 * PI * r1;
 
-        let hit_record = HitRecord::new(record_p, root, &self.material, outward_normal, r);
+        let z = if distance_squared <= radius_squared {
+            1.0 - 2.0 * r2
+        } else {
+            1.0 + r2 * ((1.0 - radius_squared / distance_squared).sqrt() - 1.0)
+        };
+        let sin_theta = (1.0 - z * z).max(0.0).sqrt();
 
-        Some(hit_record)
+        Onb::from_w(direction).local(Vec3 {
+            x: phi.cos() * sin_theta,
+            y: phi.sin() * sin_theta,
+            z,
+        })
     }
 }
\ No newline at end of file
@@ -0,0 +1,126 @@
+use std::sync::Arc;
+
+use enum_dispatch::enum_dispatch;
+use serde::{Deserialize, Serialize};
+
+use crate::framebuffer::Framebuffer;
+use crate::perlin::Perlin;
+use crate::vec3::*;
+
+// Octaves summed for turbulence and marble
+const TURBULENCE_DEPTH: u32 = 7;
+
+#[enum_dispatch(TextureEnum)]
+pub trait Texture {
+    // Color at surface coordinates (u, v) and world-space point p
+    fn value(&self, u: f64, v: f64, p: Vec3) -> Vec3;
+}
+
+#[enum_dispatch]
+#[derive(Clone)]
+pub enum TextureEnum {
+    SolidColor,
+    Checker,
+    ImageTexture,
+    NoiseTexture,
+}
+
+impl From<Vec3> for TextureEnum {
+    fn from(color: Vec3) -> TextureEnum {
+        SolidColor { color }.into()
+    }
+}
+
+#[derive(Clone)]
+pub struct SolidColor {
+    pub color: Vec3,
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: Vec3) -> Vec3 {
+        self.color
+    }
+}
+
+// 3D checkerboard of cubes with side `scale`, alternating between two textures
+#[derive(Clone)]
+pub struct Checker {
+    pub scale: f64,
+    pub even: Arc<TextureEnum>,
+    pub odd: Arc<TextureEnum>,
+}
+
+impl Texture for Checker {
+    fn value(&self, u: f64, v: f64, p: Vec3) -> Vec3 {
+        let cell = |c: f64| (c / self.scale).floor() as i64;
+        if (cell(p.x) + cell(p.y) + cell(p.z)).rem_euclid(2) == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+// Linear color image, bilinearly filtered and repeated outside [0, 1]
+#[derive(Clone)]
+pub struct ImageTexture {
+    pub image: Arc<Framebuffer>,
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: Vec3) -> Vec3 {
+        let width = self.image.width as i64;
+        let height = self.image.height as i64;
+
+        // v = 0 is the bottom row, and texel centers sit at half-integers
+        let x = u.rem_euclid(1.0) * width as f64 - 0.5;
+        let y = (1.0 - v.rem_euclid(1.0)) * height as f64 - 0.5;
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let tx = x - x0;
+        let ty = y - y0;
+
+        let texel = |dx: i64, dy: i64| {
+            let xi = (x0 as i64 + dx).rem_euclid(width) as u32;
+            let yi = (y0 as i64 + dy).rem_euclid(height) as u32;
+            self.image.get_pixel(xi, yi)
+        };
+
+        (1.0 - ty) * ((1.0 - tx) * texel(0, 0) + tx * texel(1, 0))
+            + ty * ((1.0 - tx) * texel(0, 1) + tx * texel(1, 1))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NoiseStyle {
+    // Smooth Perlin noise
+    #[default]
+    Perlin,
+    Turbulence,
+    // Sine stripes along z, distorted by turbulence
+    Marble,
+}
+
+// `scale` is the noise frequency; larger values give finer detail
+#[derive(Clone)]
+pub struct NoiseTexture {
+    pub perlin: Arc<Perlin>,
+    pub scale: f64,
+    pub style: NoiseStyle,
+    pub color: Vec3,
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _u: f64, _v: f64, p: Vec3) -> Vec3 {
+        let p = self.scale * p;
+        let intensity = match self.style {
+            NoiseStyle::Perlin => 0.5 * (1.0 + self.perlin.noise(p)),
+            NoiseStyle::Turbulence => self.perlin.turbulence(p, TURBULENCE_DEPTH),
+            NoiseStyle::Marble => {
+                0.5 * (1.0 + (p.z + 10.0 * self.perlin.turbulence(p, TURBULENCE_DEPTH)).sin())
+            }
+        };
+        intensity * self.color
+    }
+}
@@ -0,0 +1,135 @@
+use serde::{Deserialize, Serialize};
+
+// Order in which tiles are handed out to render threads
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TileOrder {
+    // Left to right, top to bottom
+    #[default]
+    Scanline,
+    // Outward from the centre of the image, where the subject usually is
+    Spiral,
+    // Along a Hilbert curve, so consecutive tiles are mostly neighbours and
+    // threads share more of the scene in cache. Grids that aren't a
+    // power-of-two square skip the curve's cells outside the image, which
+    // makes a few jumps.
+    Hilbert,
+}
+
+// Rectangle of pixels rendered as one unit of work. Tiles on the right and
+// bottom edges may be smaller than the tile size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile {
+    pub x0: u32,
+    pub y0: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Tile {
+    // The tile grown by `margin` pixels on every side, clipped to the image
+    pub fn expanded(&self, margin: u32, image_width: u32, image_height: u32) -> Tile {
+        let x0 = self.x0.saturating_sub(margin);
+        let y0 = self.y0.saturating_sub(margin);
+        Tile {
+            x0,
+            y0,
+            width: (self.x0 + self.width + margin).min(image_width) - x0,
+            height: (self.y0 + self.height + margin).min(image_height) - y0,
+        }
+    }
+
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        (self.x0..self.x0 + self.width).contains(&x) && (self.y0..self.y0 + self.height).contains(&y)
+    }
+}
+
+// Every tile of the image, each exactly once, in `order`
+pub fn tiles(image_width: u32, image_height: u32, tile_size: u32, order: TileOrder) -> Vec<Tile> {
+    let tile_size = tile_size.max(1);
+    let columns = image_width.div_ceil(tile_size);
+    let rows = image_height.div_ceil(tile_size);
+
+    let coordinates = match order {
+        TileOrder::Scanline => (0..rows)
+            .flat_map(|row| (0..columns).map(move |column| (column, row)))
+            .collect(),
+        TileOrder::Spiral => spiral(columns, rows),
+        TileOrder::Hilbert => hilbert(columns, rows),
+    };
+
+    coordinates
+        .into_iter()
+        .map(|(column, row)| {
+            let x0 = column * tile_size;
+            let y0 = row * tile_size;
+            Tile {
+                x0,
+                y0,
+                width: tile_size.min(image_width - x0),
+                height: tile_size.min(image_height - y0),
+            }
+        })
+        .collect()
+}
+
+// Square spiral from the centre tile, skipping positions outside the grid
+fn spiral(columns: u32, rows: u32) -> Vec<(u32, u32)> {
+    let count = (columns * rows) as usize;
+    let mut result = Vec::with_capacity(count);
+    let (mut x, mut y) = (((columns as i64) - 1) / 2, ((rows as i64) - 1) / 2);
+    let visit = |x: i64, y: i64, result: &mut Vec<(u32, u32)>| {
+        if (0..columns as i64).contains(&x) && (0..rows as i64).contains(&y) {
+            result.push((x as u32, y as u32));
+        }
+    };
+    visit(x, y, &mut result);
+
+    // Legs of length 1, 1, 2, 2, 3, 3, ... turning right each time
+    let directions = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+    let mut leg = 0;
+    while result.len() < count {
+        let (dx, dy) = directions[leg % 4];
+        for _ in 0..leg / 2 + 1 {
+            x += dx;
+            y += dy;
+            visit(x, y, &mut result);
+        }
+        leg += 1;
+    }
+    result
+}
+
+// Hilbert curve over the smallest power-of-two square covering the grid,
+// skipping positions outside it
+fn hilbert(columns: u32, rows: u32) -> Vec<(u32, u32)> {
+    let side = columns.max(rows).max(1).next_power_of_two() as u64;
+    (0..side * side)
+        .map(|d| hilbert_point(side, d))
+        .filter(|&(x, y)| x < columns && y < rows)
+        .collect()
+}
+
+// Position of the d-th cell along a Hilbert curve filling a side x side square
+fn hilbert_point(side: u64, d: u64) -> (u32, u32) {
+    let (mut x, mut y) = (0, 0);
+    let mut t = d;
+    let mut s = 1;
+    while s < side {
+        let rx = 1 & (t / 2);
+        let ry = 1 & (t ^ rx);
+        // Rotate the quadrant so the curve's ends line up
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - x;
+                y = s - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        x += s * rx;
+        y += s * ry;
+        t /= 4;
+        s *= 2;
+    }
+    (x as u32, y as u32)
+}
@@ -0,0 +1,105 @@
+use serde::{Deserialize, Serialize};
+
+use crate::vec3::*;
+
+// Maps scene radiance into [0, 1] before the sRGB transfer function
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ToneMapOperator {
+    // Clip anything above 1
+    #[default]
+    Clamp,
+    // x / (1 + x) per channel
+    Reinhard,
+    // Reinhard on luminance, with `white_point` as the smallest value mapped to white
+    ExtendedReinhard { white_point: f64 },
+    // Narkowicz's fit of the ACES filmic curve
+    Aces,
+    // John Hable's Uncharted 2 filmic curve
+    Hable,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ToneMapSettings {
+    #[serde(default)]
+    pub operator: ToneMapOperator,
+    // Exposure compensation in stops; each +1 doubles the brightness
+    #[serde(default)]
+    pub exposure: f64,
+}
+
+pub fn luminance(color: Vec3) -> f64 {
+    0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z
+}
+
+fn map_channels(color: Vec3, f: impl Fn(f64) -> f64) -> Vec3 {
+    Vec3 {
+        x: f(color.x),
+        y: f(color.y),
+        z: f(color.z),
+    }
+}
+
+fn hable_partial(x: f64) -> f64 {
+    const A: f64 = 0.15;
+    const B: f64 = 0.50;
+    const C: f64 = 0.10;
+    const D: f64 = 0.20;
+    const E: f64 = 0.02;
+    const F: f64 = 0.30;
+    ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F
+}
+
+impl ToneMapOperator {
+    pub fn apply(&self, color: Vec3) -> Vec3 {
+        match *self {
+            ToneMapOperator::Clamp => color,
+            ToneMapOperator::Reinhard => map_channels(color, |c| c / (1.0 + c)),
+            ToneMapOperator::ExtendedReinhard { white_point } => {
+                let l_in = luminance(color);
+                if l_in <= 0.0 {
+                    return Vec3::zeros();
+                }
+                let l_out =
+                    l_in * (1.0 + l_in / (white_point * white_point)) / (1.0 + l_in);
+                color * (l_out / l_in)
+            }
+            ToneMapOperator::Aces => map_channels(color, |c| {
+                (c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14)
+            }),
+            ToneMapOperator::Hable => {
+                const EXPOSURE_BIAS: f64 = 2.0;
+                const WHITE: f64 = 11.2;
+                let white_scale = 1.0 / hable_partial(WHITE);
+                map_channels(color, |c| hable_partial(c * EXPOSURE_BIAS) * white_scale)
+            }
+        }
+    }
+}
+
+// sRGB opto-electronic transfer function, linear [0, 1] to encoded [0, 1]
+pub fn linear_to_srgb(c: f64) -> f64 {
+    if c <= 0.0031308 {
+        12.92 * c
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+pub fn srgb_to_linear(c: f64) -> f64 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+impl ToneMapSettings {
+    // Linear radiance to display-ready sRGB in [0, 1]
+    pub fn apply(&self, color: Vec3) -> Vec3 {
+        let exposed = color * 2f64.powf(self.exposure);
+        let mapped = self.operator.apply(exposed);
+        map_channels(mapped, |c| linear_to_srgb(c.clamp(0.0, 1.0)))
+    }
+}
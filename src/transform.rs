@@ -0,0 +1,213 @@
+use std::ops;
+
+use serde::{Deserialize, Serialize};
+
+use crate::aabb::Aabb;
+use crate::ray::Ray;
+use crate::vec3::*;
+
+// Row-major 4x4 matrix acting on column vectors, so `a * b` applies b first
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Mat4 {
+    pub m: [[f64; 4]; 4],
+}
+
+impl Mat4 {
+    pub fn identity() -> Mat4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            row[i] = 1.0;
+        }
+        Mat4 { m }
+    }
+
+    pub fn translation(offset: Vec3) -> Mat4 {
+        let mut result = Mat4::identity();
+        result.m[0][3] = offset.x;
+        result.m[1][3] = offset.y;
+        result.m[2][3] = offset.z;
+        result
+    }
+
+    pub fn scaling(factors: Vec3) -> Mat4 {
+        let mut result = Mat4::identity();
+        result.m[0][0] = factors.x;
+        result.m[1][1] = factors.y;
+        result.m[2][2] = factors.z;
+        result
+    }
+
+    // Counter-clockwise rotation about `axis` when looking down the axis
+    // towards the origin (Rodrigues' formula)
+    pub fn rotation(axis: Vec3, degrees: f64) -> Mat4 {
+        let a = axis.unit_vec();
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let t = 1.0 - cos;
+
+        let mut result = Mat4::identity();
+        result.m[0][0] = t * a.x * a.x + cos;
+        result.m[0][1] = t * a.x * a.y - sin * a.z;
+        result.m[0][2] = t * a.x * a.z + sin * a.y;
+        result.m[1][0] = t * a.x * a.y + sin * a.z;
+        result.m[1][1] = t * a.y * a.y + cos;
+        result.m[1][2] = t * a.y * a.z - sin * a.x;
+        result.m[2][0] = t * a.x * a.z - sin * a.y;
+        result.m[2][1] = t * a.y * a.z + sin * a.x;
+        result.m[2][2] = t * a.z * a.z + cos;
+        result
+    }
+
+    pub fn transpose(&self) -> Mat4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.m[j][i];
+            }
+        }
+        Mat4 { m }
+    }
+
+    // Gauss-Jordan elimination with partial pivoting; None if singular
+    pub fn inverse(&self) -> Option<Mat4> {
+        let mut a = self.m;
+        let mut inv = Mat4::identity().m;
+
+        for col in 0..4 {
+            let pivot = (col..4)
+                .max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))
+                .unwrap();
+            if a[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+
+            let scale = 1.0 / a[col][col];
+            for j in 0..4 {
+                a[col][j] *= scale;
+                inv[col][j] *= scale;
+            }
+
+            for row in 0..4 {
+                if row == col {
+                    continue;
+                }
+                let factor = a[row][col];
+                for j in 0..4 {
+                    a[row][j] -= factor * a[col][j];
+                    inv[row][j] -= factor * inv[col][j];
+                }
+            }
+        }
+
+        Some(Mat4 { m: inv })
+    }
+
+    pub fn transform_point(&self, p: Vec3) -> Vec3 {
+        let m = &self.m;
+        let x = m[0][0] * p.x + m[0][1] * p.y + m[0][2] * p.z + m[0][3];
+        let y = m[1][0] * p.x + m[1][1] * p.y + m[1][2] * p.z + m[1][3];
+        let z = m[2][0] * p.x + m[2][1] * p.y + m[2][2] * p.z + m[2][3];
+        let w = m[3][0] * p.x + m[3][1] * p.y + m[3][2] * p.z + m[3][3];
+        if w == 1.0 {
+            Vec3 { x, y, z }
+        } else {
+            Vec3 { x, y, z } / w
+        }
+    }
+
+    // Ignores the translation part
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3 {
+            x: m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            y: m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            z: m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        }
+    }
+}
+
+impl ops::Mul<Mat4> for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, rhs: Mat4) -> Mat4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[i][k] * rhs.m[k][j]).sum();
+            }
+        }
+        Mat4 { m }
+    }
+}
+
+// Object-to-world matrix together with its inverse, and the inverse
+// transpose for normals
+#[derive(Debug, Clone, Copy)]
+pub struct Transform {
+    pub matrix: Mat4,
+    pub inverse: Mat4,
+    pub inverse_transpose: Mat4,
+}
+
+impl Transform {
+    // None if the matrix can't be inverted, e.g. a scale by zero
+    pub fn new(matrix: Mat4) -> Option<Transform> {
+        Some(Transform::from_inverse(matrix, matrix.inverse()?))
+    }
+
+    fn from_inverse(matrix: Mat4, inverse: Mat4) -> Transform {
+        Transform {
+            matrix,
+            inverse,
+            inverse_transpose: inverse.transpose(),
+        }
+    }
+
+    pub fn identity() -> Transform {
+        Transform::from_inverse(Mat4::identity(), Mat4::identity())
+    }
+
+    // This transform followed by `next`
+    pub fn then(&self, next: &Transform) -> Transform {
+        Transform::from_inverse(next.matrix * self.matrix, self.inverse * next.inverse)
+    }
+
+    pub fn point(&self, p: Vec3) -> Vec3 {
+        self.matrix.transform_point(p)
+    }
+
+    pub fn vector(&self, v: Vec3) -> Vec3 {
+        self.matrix.transform_vector(v)
+    }
+
+    // Normals stay perpendicular to the surface under non-uniform scaling
+    // when transformed by the inverse transpose. The result is not normalized.
+    pub fn normal(&self, n: Vec3) -> Vec3 {
+        self.inverse_transpose.transform_vector(n)
+    }
+
+    // World-space ray to object space. The direction is not normalized, so
+    // hit distances are the same in both spaces.
+    pub fn inverse_ray(&self, r: &Ray) -> Ray {
+        Ray {
+            origin: self.inverse.transform_point(r.origin),
+            direction: self.inverse.transform_vector(r.direction),
+            time: r.time,
+        }
+    }
+
+    // Box around the transformed corners of `bbox`
+    pub fn bounding_box(&self, bbox: &Aabb) -> Aabb {
+        let mut result = Aabb::empty();
+        for i in 0..8 {
+            let corner = Vec3 {
+                x: if i & 1 == 0 { bbox.minimum.x } else { bbox.maximum.x },
+                y: if i & 2 == 0 { bbox.minimum.y } else { bbox.maximum.y },
+                z: if i & 4 == 0 { bbox.minimum.z } else { bbox.maximum.z },
+            };
+            result = result.including(self.point(corner));
+        }
+        result
+    }
+}
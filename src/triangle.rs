@@ -0,0 +1,174 @@
+use crate::aabb::Aabb;
+use crate::hit::*;
+use crate::material::{Material, MaterialEnum};
+use crate::sampler::{Sampler, SamplerEnum};
+use crate::ray::*;
+use crate::vec3::*;
+
+// Keeps axis-aligned triangles from producing zero-thickness boxes
+const BOX_PADDING: f64 = 1e-4;
+// Relative to the lengths of the edges and the ray direction, which the
+// determinant scales with, so the parallel test works at any scale
+const DETERMINANT_EPSILON: f64 = 1e-12;
+
+pub struct Triangle {
+    pub vertices: [Vec3; 3],
+    pub material: MaterialEnum,
+}
+
+// Moller-Trumbore ray/triangle intersection.
+// Returns t and the barycentric weights of the three vertices.
+pub fn intersect_triangle(
+    r: &Ray,
+    vertices: &[Vec3; 3],
+    t_min: f64,
+    t_max: f64,
+) -> Option<(f64, [f64; 3])> {
+    let edge1 = vertices[1] - vertices[0];
+    let edge2 = vertices[2] - vertices[0];
+
+    let p_vec = r.direction.cross(edge2);
+    let determinant = edge1.dot(p_vec);
+    let scale = edge1.length() * edge2.length() * r.direction.length();
+    if determinant.abs() <= DETERMINANT_EPSILON * scale {
+        // Ray is parallel to the triangle's plane
+        return None;
+    }
+    let inv_determinant = 1.0 / determinant;
+
+    let t_vec = r.origin - vertices[0];
+    let b1 = t_vec.dot(p_vec) * inv_determinant;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+
+    let q_vec = t_vec.cross(edge1);
+    let b2 = r.direction.dot(q_vec) * inv_determinant;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+
+    let t = edge2.dot(q_vec) * inv_determinant;
+    if t < t_min || t > t_max {
+        return None;
+    }
+
+    Some((t, [1.0 - b1 - b2, b1, b2]))
+}
+
+// Solid angle density of sampling the triangle uniformly by area, as seen
+// from `origin`. Zero if the ray misses the triangle.
+pub fn triangle_pdf_value(vertices: &[Vec3; 3], origin: Vec3, direction: Vec3) -> f64 {
+    let r = Ray {
+        origin,
+        direction,
+        time: 0.0,
+    };
+    let Some((t, _)) = intersect_triangle(&r, vertices, 0.001, f64::INFINITY) else {
+        return 0.0;
+    };
+
+    let area_normal = (vertices[1] - vertices[0]).cross(vertices[2] - vertices[0]);
+    let area = 0.5 * area_normal.length();
+    let distance_squared = t * t * direction.length_squared();
+    let cosine = (area_normal.dot(direction) / (area_normal.length() * direction.length())).abs();
+    if cosine <= 0.0 {
+        return 0.0;
+    }
+    distance_squared / (cosine * area)
+}
+
+// Direction from `origin` to a point chosen uniformly by area
+pub fn triangle_random_direction(
+    vertices: &[Vec3; 3],
+    origin: Vec3,
+    sampler: &mut SamplerEnum,
+) -> Vec3 {
+    let (r1, r2) = sampler.get_2d();
+    let sqrt_r1 = r1.sqrt();
+    let b1 = sqrt_r1 * (1.0 - r2);
+    let b2 = sqrt_r1 * r2;
+    let point = vertices[0] + b1 * (vertices[1] - vertices[0]) + b2 * (vertices[2] - vertices[0]);
+    point - origin
+}
+
+pub fn triangle_bounding_box(vertices: &[Vec3; 3]) -> Aabb {
+    let padding = Vec3 {
+        x: BOX_PADDING,
+        y: BOX_PADDING,
+        z: BOX_PADDING,
+    };
+    let bbox = vertices
+        .iter()
+        .fold(Aabb::empty(), |acc, &vertex| acc.including(vertex));
+    Aabb::new(bbox.minimum - padding, bbox.maximum + padding)
+}
+
+impl Hit for Triangle {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (t, barycentric) = intersect_triangle(r, &self.vertices, t_min, t_max)?;
+
+        let outward_normal = (self.vertices[1] - self.vertices[0])
+            .cross(self.vertices[2] - self.vertices[0])
+            .unit_vec();
+
+        let mut hit_record = HitRecord::new(r.at(t), t, &self.material, outward_normal, r);
+        hit_record.barycentric = Some(barycentric);
+        // Without per-vertex UVs the triangle maps to the lower-left half of the unit square
+        hit_record.u = barycentric[1];
+        hit_record.v = barycentric[2];
+
+        Some(hit_record)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(triangle_bounding_box(&self.vertices))
+    }
+
+    fn is_light(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f64 {
+        triangle_pdf_value(&self.vertices, origin, direction)
+    }
+
+    fn random_direction(&self, origin: Vec3, sampler: &mut SamplerEnum) -> Vec3 {
+        triangle_random_direction(&self.vertices, origin, sampler)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scaled_triangle(scale: f64) -> [Vec3; 3] {
+        [
+            Vec3::zeros(),
+            Vec3 { x: scale, y: 0.0, z: 0.0 },
+            Vec3 { x: 0.0, y: scale, z: 0.0 },
+        ]
+    }
+
+    #[test]
+    fn parallel_test_does_not_depend_on_scale() {
+        for scale in [1e-7, 1.0, 1e7] {
+            let vertices = scaled_triangle(scale);
+            let centre = Vec3 { x: scale / 4.0, y: scale / 4.0, z: 0.0 };
+            let hit = Ray {
+                origin: centre - Vec3 { x: 0.0, y: 0.0, z: 1.0 },
+                direction: Vec3 { x: 0.0, y: 0.0, z: 1.0 },
+                time: 0.0,
+            };
+            let (t, _) = intersect_triangle(&hit, &vertices, 0.0, f64::INFINITY).unwrap();
+            assert!((t - 1.0).abs() < 1e-9, "scale {}", scale);
+
+            let parallel = Ray {
+                origin: centre - Vec3 { x: scale, y: 0.0, z: 0.0 },
+                direction: Vec3 { x: 1.0, y: 0.0, z: 0.0 },
+                time: 0.0,
+            };
+            assert!(intersect_triangle(&parallel, &vertices, 0.0, f64::INFINITY).is_none());
+        }
+    }
+}
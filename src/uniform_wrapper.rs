@@ -1,64 +1,121 @@
 use rand::{
     distributions::{DistIter, Distribution, Uniform},
-    prelude::{Rng, ThreadRng, thread_rng},
+    prelude::{SeedableRng, thread_rng},
 };
+use rand_pcg::Pcg64Mcg;
+
+// PCG's output is fixed by its algorithm, so a seed gives the same
+// numbers on every platform and rand version
+pub type UniRng = Pcg64Mcg;
 
 pub trait UniGen {
     fn sample(&mut self) -> f64;
 }
 
+// SplitMix64 finalizer, scrambles every input bit into every output bit
+fn mix64(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+// Seed for an independent stream identified by `keys`, e.g. a pixel's
+// coordinates. Lets every pixel draw the same numbers no matter which
+// thread renders it or in what order.
+pub fn stream_seed(seed: u64, keys: &[u64]) -> u64 {
+    keys.iter().fold(mix64(seed), |acc, &key| {
+        mix64(acc ^ key.wrapping_add(0x9e3779b97f4a7c15))
+    })
+}
+
 
 pub struct UniGen0_1 {
-    dist_iter: DistIter<Uniform<f64>, ThreadRng, f64>,
+    uniform: Uniform<f64>,
+    rng: UniRng,
 }
 
 impl UniGen0_1 {
     pub fn new() -> Self {
-        let uniform = Uniform::new(0.0, 1.0);
-        let rng = thread_rng();
+        Self::from_rng(UniRng::from_rng(thread_rng()).unwrap())
+    }
+
+    pub fn seeded(seed: u64) -> Self {
+        Self::from_rng(UniRng::seed_from_u64(seed))
+    }
+
+    // Carries on from a generator's saved state
+    pub fn from_rng(rng: UniRng) -> Self {
         Self {
-            dist_iter: uniform.sample_iter(rng),
+            uniform: Uniform::new(0.0, 1.0),
+            rng,
         }
     }
+
+    pub fn into_rng(self) -> UniRng {
+        self.rng
+    }
+}
+
+impl Default for UniGen0_1 {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl UniGen for UniGen0_1 {
     fn sample(&mut self) -> f64 {
-        // dist_iter.next always returns Some, so hopefully this should never panic
-        self.dist_iter.next().unwrap()
+        self.uniform.sample(&mut self.rng)
     }
 }
 
 pub struct UniGenNeg1_1 {
-    dist_iter: DistIter<Uniform<f64>, ThreadRng, f64>,
+    uniform: Uniform<f64>,
+    rng: UniRng,
 }
 
 impl UniGenNeg1_1 {
     pub fn new() -> Self {
-        let uniform = Uniform::new(-1.0, 1.0);
-        let rng = thread_rng();
+        Self::from_rng(UniRng::from_rng(thread_rng()).unwrap())
+    }
+
+    pub fn seeded(seed: u64) -> Self {
+        Self::from_rng(UniRng::seed_from_u64(seed))
+    }
+
+    // Carries on from a generator's saved state
+    pub fn from_rng(rng: UniRng) -> Self {
         Self {
-            dist_iter: uniform.sample_iter(rng),
+            uniform: Uniform::new(-1.0, 1.0),
+            rng,
         }
     }
+
+    pub fn into_rng(self) -> UniRng {
+        self.rng
+    }
+}
+
+impl Default for UniGenNeg1_1 {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl UniGen for UniGenNeg1_1 {
     fn sample(&mut self) -> f64 {
-        // dist_iter.next always returns Some, so hopefully this should never panic
-        self.dist_iter.next().unwrap()
+        self.uniform.sample(&mut self.rng)
     }
 }
 
 
 pub struct UniGenUntyped {
-    dist_iter: DistIter<Uniform<f64>, ThreadRng, f64>,
+    dist_iter: DistIter<Uniform<f64>, UniRng, f64>,
 }
 
 impl UniGenUntyped {
-    pub fn new(start: f64, end: f64) -> Self {
+    pub fn seeded(start: f64, end: f64, seed: u64) -> Self {
         let uniform = Uniform::new(start, end);
-        let rng = thread_rng();
+        let rng = UniRng::seed_from_u64(seed);
         Self {
             dist_iter: uniform.sample_iter(rng),
         }
@@ -1,10 +1,15 @@
 use std::ops;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
 
 use crate::uniform_wrapper::*;
 // use num::Float;
 
 
-#[derive(Debug, Default, Clone, Copy)]
+// Serialized as a plain [x, y, z] array
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(from = "[f64; 3]", into = "[f64; 3]")]
 pub struct Vec3 {
     pub x: f64,
     pub y: f64,
@@ -46,6 +51,29 @@ impl Vec3 {
         }
     }
 
+    // The warps below map uniform samples from a Sampler to uniform points,
+    // without rejection so stratified samples stay stratified
+
+    // On the unit sphere's surface
+    pub fn unit_vector_from(u: (f64, f64)) -> Vec3 {
+        let z = 1.0 - 2.0 * u.0;
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let (sin, cos) = (2.0 * std::f64::consts::PI * u.1).sin_cos();
+        Vec3 {x: r * cos, y: r * sin, z}
+    }
+
+    // Inside the unit sphere; `u_radius` picks the distance from the centre
+    pub fn in_unit_sphere_from(u: (f64, f64), u_radius: f64) -> Vec3 {
+        u_radius.cbrt() * Vec3::unit_vector_from(u)
+    }
+
+    // Inside the unit disk in the xy plane
+    pub fn in_unit_disk_from(u: (f64, f64)) -> Vec3 {
+        let r = u.0.sqrt();
+        let (sin, cos) = (2.0 * std::f64::consts::PI * u.1).sin_cos();
+        Vec3 {x: r * cos, y: r * sin, z: 0.0}
+    }
+
     // Should add some strong typing to this random range
     pub fn random_in_unit_disk(unigen: &mut UniGenNeg1_1) -> Vec3 {
         loop {
@@ -140,8 +168,48 @@ impl Refract for Vec3 {
     }
 }
 
+impl From<[f64; 3]> for Vec3 {
+    fn from(a: [f64; 3]) -> Vec3 {
+        Vec3 { x: a[0], y: a[1], z: a[2] }
+    }
+}
+
+impl From<Vec3> for [f64; 3] {
+    fn from(v: Vec3) -> [f64; 3] {
+        [v.x, v.y, v.z]
+    }
+}
+
+// Parses "x,y,z", as used on the command line
+impl FromStr for Vec3 {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Vec3, String> {
+        let components = s
+            .split(',')
+            .map(|c| c.trim().parse::<f64>())
+            .collect::<Result<Vec<f64>, _>>()
+            .map_err(|err| format!("invalid vector '{}': {}", s, err))?;
+        match components.as_slice() {
+            &[x, y, z] => Ok(Vec3 { x, y, z }),
+            _ => Err(format!("expected 3 comma-separated numbers, got '{}'", s)),
+        }
+    }
+}
+
 // Operator Overloads
 
+impl ops::Index<usize> for Vec3 {
+    type Output = f64;
+    fn index(&self, axis: usize) -> &f64 {
+        match axis {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Vec3 axis out of range: {}", axis),
+        }
+    }
+}
+
 impl ops::Add<Vec3> for Vec3 {
     type Output = Vec3;
     fn add(self, rhs: Vec3) -> Self::Output {